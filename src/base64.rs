@@ -1,64 +1,221 @@
-extern crate bit_vec;
+use bit_vec::BitVec;
+use std::error::Error;
+use std::fmt;
 
-use self::bit_vec::BitVec;
-pub fn from_u8(n: u8, len: usize) -> BitVec {
-    let mut b = BitVec::new();
-    let mlb = (1 << (len - 1)) as u8;
-    for i in 0..len {
-        let mask = (mlb >> i) as u8;
-        b.push((n & mask) != 0);
-    }
-    b
+/// Characters for the values 62 and 63; the first 62 are always `A-Z`, `a-z`, `0-9`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alphabet {
+    /// `+` and `/`, as used by the quoridorfansite tools.
+    Standard,
+    /// `-` and `_`, safe in URLs and file names.
+    UrlSafe,
 }
 
-pub fn from_u16(n: u16, len: usize) -> BitVec {
-    let mut b = BitVec::new();
-    let mlb = (1 << (len - 1)) as u16;
-    for i in 0..len {
-        let mask = (mlb >> i) as u16;
-        b.push((n & mask) != 0);
+impl Alphabet {
+    fn char(self, n: u8) -> char {
+        match n {
+            n if n < 26 => (b'A' + n) as char,
+            n if n < 52 => (b'a' + (n - 26)) as char,
+            n if n < 62 => (b'0' + (n - 52)) as char,
+            62 if self == Alphabet::Standard => '+',
+            62 => '-',
+            _ if self == Alphabet::Standard => '/',
+            _ => '_',
+        }
+    }
+
+    fn value(self, c: char) -> Option<u8> {
+        match (c, self) {
+            ('A'..='Z', _) => Some(c as u8 - b'A'),
+            ('a'..='z', _) => Some(c as u8 - b'a' + 26),
+            ('0'..='9', _) => Some(c as u8 - b'0' + 52),
+            ('+', Alphabet::Standard) | ('-', Alphabet::UrlSafe) => Some(62),
+            ('/', Alphabet::Standard) | ('_', Alphabet::UrlSafe) => Some(63),
+            _ => None,
+        }
     }
-    b
 }
 
-fn u8_to_char(n: u8) -> char {
-    match n {
-        n if n < 26 => ('A' as u8 + n) as char,
-        n if (26 <= n && n < 52) => ('a' as u8 + (n - 26)) as char,
-        n if (52 <= n && n < 62) => ('0' as u8 + (n - 52)) as char,
-        62 => '+',
-        63 => '/',
-        _ => {
-            panic!("something wrong:{}", n);
+/// Why a code could not be read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecodeError {
+    /// The character at this index is not in the alphabet.
+    InvalidChar(usize, char),
+    /// A field runs past the last bit.
+    UnexpectedEnd,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidChar(i, c) => write!(f, "invalid character {:?} at {}", c, i),
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of data"),
         }
     }
 }
 
-pub fn append(src: &mut BitVec, dst: BitVec) {
-    for b in dst.iter() {
-        src.push(b);
+impl Error for DecodeError {}
+
+/// Packs fields of any width, most significant bit first.
+#[derive(Debug, Clone, Default)]
+pub struct BitWriter {
+    bits: BitVec,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        BitWriter::default()
+    }
+
+    /// Writes the low `width` bits of `value`. `width` is at most 64.
+    pub fn write(&mut self, value: u64, width: usize) {
+        debug_assert!(width <= 64);
+        for i in (0..width).rev() {
+            self.bits.push(value >> i & 1 != 0);
+        }
+    }
+
+    pub fn write_bool(&mut self, b: bool) {
+        self.bits.push(b);
+    }
+
+    /// Number of bits written so far.
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    pub fn into_bitvec(self) -> BitVec {
+        self.bits
+    }
+
+    /// Six bits per character, the last one padded with zero bits. No `=` is added.
+    pub fn to_base64(&self, alphabet: Alphabet) -> String {
+        let mut s = String::new();
+        for start in (0..self.bits.len()).step_by(6) {
+            let mut n = 0;
+            for i in start..start + 6 {
+                n = n << 1 | self.bits.get(i).unwrap_or(false) as u8;
+            }
+            s.push(alphabet.char(n));
+        }
+        s
     }
 }
 
-pub fn bitvec_to_base64(mut bv: BitVec) -> String {
-    let mut charv = Vec::new();
-    let mut n = 0;
-    while bv.len() % 6 != 0 {
-        bv.push(false);
-    }
-    for (i, b) in bv.iter().enumerate() {
-        if i != 0 && i % 6 == 0 {
-            let c = u8_to_char(n);
-            charv.push(c);
-            n = 0;
+/// Reads back what a [`BitWriter`] wrote.
+#[derive(Debug, Clone)]
+pub struct BitReader {
+    bits: BitVec,
+    pos: usize,
+}
+
+impl BitReader {
+    pub fn new(bits: BitVec) -> Self {
+        BitReader { bits, pos: 0 }
+    }
+
+    /// Decodes `s`; the padding bits of the last character are kept.
+    pub fn from_base64(s: &str, alphabet: Alphabet) -> Result<Self, DecodeError> {
+        let mut w = BitWriter::new();
+        for (i, c) in s.chars().enumerate() {
+            let n = alphabet.value(c).ok_or(DecodeError::InvalidChar(i, c))?;
+            w.write(n as u64, 6);
         }
-        if b {
-            n = n * 2 + 1;
-        } else {
-            n = n * 2;
+        Ok(BitReader::new(w.into_bitvec()))
+    }
+
+    /// Reads a `width`-bit field. `width` is at most 64.
+    pub fn read(&mut self, width: usize) -> Result<u64, DecodeError> {
+        debug_assert!(width <= 64);
+        if self.remaining() < width {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let mut n = 0;
+        for _ in 0..width {
+            n = n << 1 | self.bits[self.pos] as u64;
+            self.pos += 1;
+        }
+        Ok(n)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, DecodeError> {
+        Ok(self.read(1)? == 1)
+    }
+
+    /// Number of bits not read yet.
+    pub fn remaining(&self) -> usize {
+        self.bits.len() - self.pos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testutil::Rng;
+
+    #[test]
+    fn round_trip() {
+        let mut rng = Rng::new(18);
+        for &alphabet in &[Alphabet::Standard, Alphabet::UrlSafe] {
+            for _ in 0..100 {
+                let mut fields = Vec::new();
+                let mut w = BitWriter::new();
+                for _ in 0..rng.below(20) {
+                    let width = [1, 3, 7, 10, 64][rng.below(5)];
+                    let value = match width {
+                        64 => rng.next_u64(),
+                        _ => rng.next_u64() & ((1 << width) - 1),
+                    };
+                    w.write(value, width);
+                    fields.push((value, width));
+                }
+                let bits = w.len();
+                let code = w.to_base64(alphabet);
+                assert_eq!(code.len(), bits.div_ceil(6));
+                let mut r = BitReader::from_base64(&code, alphabet).unwrap();
+                for &(value, width) in &fields {
+                    assert_eq!(r.read(width), Ok(value));
+                }
+                // 最後の文字の埋め草だけが残る
+                assert!(r.remaining() < 6);
+            }
         }
     }
-    let c = u8_to_char(n);
-    charv.push(c);
-    charv.iter().collect()
+
+    #[test]
+    fn alphabets() {
+        let mut w = BitWriter::new();
+        w.write(62, 6);
+        w.write(63, 6);
+        assert_eq!(w.to_base64(Alphabet::Standard), "+/");
+        assert_eq!(w.to_base64(Alphabet::UrlSafe), "-_");
+        assert_eq!(
+            BitReader::from_base64("+/", Alphabet::UrlSafe).err(),
+            Some(DecodeError::InvalidChar(0, '+'))
+        );
+        assert_eq!(
+            BitReader::from_base64("A-", Alphabet::Standard).err(),
+            Some(DecodeError::InvalidChar(1, '-'))
+        );
+    }
+
+    #[test]
+    fn unexpected_end() {
+        let mut w = BitWriter::new();
+        w.write_bool(true);
+        w.write(5, 3);
+        let mut r =
+            BitReader::from_base64(&w.to_base64(Alphabet::Standard), Alphabet::Standard).unwrap();
+        assert_eq!(r.read_bool(), Ok(true));
+        assert_eq!(r.read(3), Ok(5));
+        assert_eq!(r.read(3), Err(DecodeError::UnexpectedEnd));
+        // 失敗しても読み位置は進まない
+        assert_eq!(r.read(2), Ok(0));
+        assert_eq!(r.remaining(), 0);
+        assert_eq!(r.read_bool(), Err(DecodeError::UnexpectedEnd));
+    }
 }
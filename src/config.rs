@@ -0,0 +1,155 @@
+/// Smallest supported board side.
+pub const MIN_SIZE: usize = 3;
+/// Largest supported board side.
+pub const MAX_SIZE: usize = 11;
+/// Most players a game can seat.
+pub const MAX_PLAYERS: usize = 4;
+/// Walls shared out between the players when no count is given.
+const TOTAL_WALLS: usize = 20;
+
+/// An edge of the board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Top => Side::Bottom,
+            Side::Bottom => Side::Top,
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+
+    /// Whether `(y, x)` lies on this edge of a `height` x `width` board.
+    pub fn contains(self, height: usize, width: usize, (y, x): (usize, usize)) -> bool {
+        match self {
+            Side::Top => y == 0,
+            Side::Bottom => y == height - 1,
+            Side::Left => x == 0,
+            Side::Right => x == width - 1,
+        }
+    }
+}
+
+/// When a game ends without a winner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawRules {
+    /// Draw once the same position, side to move included, has occurred this many times.
+    pub repetition: Option<usize>,
+    /// Draw once this many plies have been played.
+    pub max_plies: Option<usize>,
+    /// Whether the players may agree to a draw.
+    pub agreement: bool,
+}
+
+impl Default for DrawRules {
+    fn default() -> Self {
+        DrawRules {
+            repetition: Some(3),
+            max_plies: None,
+            agreement: false,
+        }
+    }
+}
+
+/// Board geometry and wall budget of a game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameConfig {
+    pub height: usize,
+    pub width: usize,
+    /// Walls each player starts with.
+    pub wall_limit: usize,
+    /// 2 or 4. Players move in seat order, clockwise from the bottom edge.
+    pub player_num: usize,
+    pub draw: DrawRules,
+}
+
+impl GameConfig {
+    /// A game on a `size` x `size` board.
+    pub fn new(size: usize, wall_limit: usize, player_num: usize) -> Result<Self, String> {
+        let config = GameConfig {
+            height: size,
+            width: size,
+            wall_limit,
+            player_num,
+            draw: DrawRules::default(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Walls per player when they share the usual 20 walls.
+    pub fn default_wall_limit(player_num: usize) -> usize {
+        TOTAL_WALLS / player_num
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for &n in &[self.height, self.width] {
+            if !(MIN_SIZE..=MAX_SIZE).contains(&n) {
+                return Err(format!(
+                    "Board size must be between {} and {}",
+                    MIN_SIZE, MAX_SIZE
+                ));
+            }
+            if n % 2 == 0 {
+                return Err("Board size must be odd".to_string());
+            }
+        }
+        if self.player_num != 2 && self.player_num != MAX_PLAYERS {
+            return Err("Number of players must be 2 or 4".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn in_area(&self, y: usize, x: usize) -> bool {
+        y < self.height && x < self.width
+    }
+
+    /// Edge player `id` starts on.
+    pub fn home(&self, id: usize) -> Side {
+        if self.player_num == 2 {
+            [Side::Bottom, Side::Top][id]
+        } else {
+            [Side::Bottom, Side::Left, Side::Top, Side::Right][id]
+        }
+    }
+
+    /// Edge player `id` has to reach.
+    pub fn goal(&self, id: usize) -> Side {
+        self.home(id).opposite()
+    }
+
+    /// Starting square of player `id`, in the middle of its home edge.
+    pub fn start(&self, id: usize) -> (usize, usize) {
+        let (h, w) = (self.height, self.width);
+        match self.home(id) {
+            Side::Top => (0, w / 2),
+            Side::Bottom => (h - 1, w / 2),
+            Side::Left => (h / 2, 0),
+            Side::Right => (h / 2, w - 1),
+        }
+    }
+
+    /// Whether `(y, x)` lies on `side`.
+    pub fn on_side(&self, pos: (usize, usize), side: Side) -> bool {
+        side.contains(self.height, self.width, pos)
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            height: 9,
+            width: 9,
+            wall_limit: 10,
+            player_num: 2,
+            draw: DrawRules::default(),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::error::Error;
+use std::fmt;
+use wall::Colour;
+
+/// Why an action was refused.
+///
+/// Coordinates are `(y, x)` as in [`Command`](::Command); walls are given by their centre.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IllegalAction {
+    /// The player has put all of its walls.
+    NoWallsLeft,
+    WallOutOfBounds(usize, usize),
+    /// The wall would share a segment with a wall on the board.
+    WallOverlap(usize, usize),
+    /// The wall would cross a wall with the same centre.
+    WallCrossing(usize, usize),
+    /// The wall would cut player `player` off from its goal edge.
+    BlocksPath {
+        y: usize,
+        x: usize,
+        player: usize,
+    },
+    MoveOutOfBounds(usize, usize),
+    /// Another pawn stands on the square, or it is the mover's own square.
+    SquareOccupied(usize, usize),
+    /// A wall stands between the pawn and the adjacent square.
+    WallInTheWay(usize, usize),
+    /// The square is next to a pawn the mover touches, but the jump rules forbid it.
+    JumpBlocked(usize, usize),
+    /// The square is not one move away.
+    NotAdjacent(usize, usize),
+    /// Player `turn` is the side to move.
+    NotYourTurn(usize),
+    DrawNotAllowed,
+}
+
+impl IllegalAction {
+    /// Number identifying the kind of error in the player protocol.
+    ///
+    /// 1xx are wall errors, 2xx pawn errors and 3xx other requests.
+    pub fn code(&self) -> u16 {
+        match self {
+            IllegalAction::NoWallsLeft => 101,
+            IllegalAction::WallOutOfBounds(..) => 102,
+            IllegalAction::WallOverlap(..) => 103,
+            IllegalAction::WallCrossing(..) => 104,
+            IllegalAction::BlocksPath { .. } => 105,
+            IllegalAction::MoveOutOfBounds(..) => 201,
+            IllegalAction::SquareOccupied(..) => 202,
+            IllegalAction::WallInTheWay(..) => 203,
+            IllegalAction::JumpBlocked(..) => 204,
+            IllegalAction::NotAdjacent(..) => 205,
+            IllegalAction::NotYourTurn(_) => 301,
+            IllegalAction::DrawNotAllowed => 302,
+        }
+    }
+}
+
+// 座標は入力形式と同じ x y の順で書く
+impl fmt::Display for IllegalAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IllegalAction::NoWallsLeft => write!(f, "You have no wall"),
+            IllegalAction::WallOutOfBounds(y, x) => {
+                write!(f, "Wall at {} {} is out of bounds", x, y)
+            }
+            IllegalAction::WallOverlap(y, x) => {
+                write!(f, "Wall at {} {} overlaps another wall", x, y)
+            }
+            IllegalAction::WallCrossing(y, x) => {
+                write!(f, "Wall at {} {} crosses another wall", x, y)
+            }
+            IllegalAction::BlocksPath { y, x, player } => write!(
+                f,
+                "Wall at {} {} blocks every path of {}",
+                x,
+                y,
+                Colour::from_index(player)
+            ),
+            IllegalAction::MoveOutOfBounds(y, x) => {
+                write!(f, "Position {} {} is out of bounds", x, y)
+            }
+            IllegalAction::SquareOccupied(y, x) => write!(f, "Square {} {} is occupied", x, y),
+            IllegalAction::WallInTheWay(y, x) => {
+                write!(f, "A wall blocks the move to {} {}", x, y)
+            }
+            IllegalAction::JumpBlocked(y, x) => write!(f, "Cannot jump to {} {}", x, y),
+            IllegalAction::NotAdjacent(y, x) => {
+                write!(f, "Cannot reach {} {} in one move", x, y)
+            }
+            IllegalAction::NotYourTurn(turn) => {
+                write!(f, "It is {}'s turn", Colour::from_index(turn))
+            }
+            IllegalAction::DrawNotAllowed => write!(f, "Draw by agreement is not allowed"),
+        }
+    }
+}
+
+impl Error for IllegalAction {}
@@ -0,0 +1,124 @@
+//! The greeting a player sends right after connecting.
+use protocol::ProtocolError;
+use quoridor_judge::{Colour, GameConfig};
+use std::time::Duration;
+
+/// Newest protocol version the judge speaks.
+///
+/// Version 2 clients get the state from `Quoridor::socketformat_v2` instead of the
+/// legacy format.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+const MAX_NAME_LEN: usize = 32;
+
+pub struct Hello {
+    pub name: String,
+    /// Version the client asked for; 1 if it gave none.
+    pub version: u32,
+}
+
+impl Hello {
+    /// Reads `HELLO <name> [version]`. Returns `None` if the line is not a greeting.
+    ///
+    /// Names may not contain `:`, which separates chat messages.
+    pub fn parse(line: &str) -> Option<Result<Hello, ProtocolError>> {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("HELLO") {
+            return None;
+        }
+        let hello = match (words.next(), words.next(), words.next()) {
+            (Some(name), version, None) if name.len() <= MAX_NAME_LEN && !name.contains(':') => {
+                match version.map(|v| v.parse::<u32>()) {
+                    None => Some(1),
+                    Some(Ok(v)) if v >= 1 => Some(v),
+                    Some(_) => None,
+                }
+                .map(|version| Hello {
+                    name: name.to_string(),
+                    version,
+                })
+            }
+            _ => None,
+        };
+        Some(hello.ok_or(ProtocolError::BadHello))
+    }
+
+    /// The version both sides speak.
+    pub fn negotiated(&self) -> u32 {
+        self.version.min(PROTOCOL_VERSION)
+    }
+}
+
+/// The judge's answer to a greeting. `time` is each player's time for the whole game in
+/// seconds, or `none`.
+pub fn welcome(
+    version: u32,
+    seat: usize,
+    config: &GameConfig,
+    time_limit: Option<Duration>,
+) -> String {
+    let time = match time_limit {
+        Some(limit) => limit.as_secs().to_string(),
+        None => "none".to_string(),
+    };
+    format!(
+        "WELCOME version={} seat={} colour={} height={} width={} walls={} players={} time={}\n",
+        version,
+        seat,
+        Colour::from_index(seat),
+        config.height,
+        config.width,
+        config.wall_limit,
+        config.player_num,
+        time
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(line: &str) -> (String, u32) {
+        let hello = Hello::parse(line).unwrap().unwrap();
+        (hello.name, hello.version)
+    }
+
+    fn bad(line: &str) -> bool {
+        Hello::parse(line).unwrap().err() == Some(ProtocolError::BadHello)
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(hello("HELLO bob"), ("bob".to_string(), 1));
+        assert_eq!(hello("HELLO bob 2"), ("bob".to_string(), 2));
+        assert_eq!(hello(&format!("HELLO {}", "a".repeat(32))).1, 1);
+        assert!(bad("HELLO"));
+        assert!(bad("HELLO bob 0"));
+        assert!(bad("HELLO bob two"));
+        assert!(bad("HELLO bob -1"));
+        assert!(bad("HELLO bob:x"));
+        assert!(bad(&format!("HELLO {}", "a".repeat(33))));
+        assert!(bad("HELLO bob 2 extra"));
+        assert!(Hello::parse("e2").is_none());
+        assert!(Hello::parse("hello bob").is_none());
+        assert!(Hello::parse("").is_none());
+    }
+
+    #[test]
+    fn negotiated() {
+        let hello = |line| Hello::parse(line).unwrap().unwrap().negotiated();
+        assert_eq!(hello("HELLO bob"), 1);
+        assert_eq!(hello("HELLO bob 2"), PROTOCOL_VERSION);
+        assert_eq!(hello("HELLO bob 99"), PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn welcome_line() {
+        let config = GameConfig::default();
+        assert_eq!(
+            welcome(2, 1, &config, Some(Duration::from_secs(300))),
+            "WELCOME version=2 seat=1 colour=black height=9 width=9 walls=10 players=2 time=300\n"
+        );
+        assert!(welcome(1, 0, &config, None).ends_with(" time=none\n"));
+    }
+}
@@ -0,0 +1,37 @@
+//! Quoridor rules engine shared by the judge server, bots and analysis tools.
+//!
+//! A game is held in a [`Quoridor`]: list legal actions with
+//! [`Quoridor::legal_actions`] or check one with [`Quoridor::movable`] and
+//! [`Quoridor::settable`], play them with [`Quoridor::play`], take them back with
+//! [`Quoridor::undo`] and check the result with [`Quoridor::is_over`]. Searches can use
+//! the unchecked [`Quoridor::apply`] and [`Quoridor::unapply`] instead.
+//! [`Quoridor::distance`] measures how far each player is from its goal, and
+//! [`Quoridor::perft`] counts the game tree to check the move generator.
+extern crate bit_vec;
+
+pub mod base64;
+mod config;
+mod error;
+mod notation;
+mod outcome;
+mod path;
+mod perft;
+mod quoridor;
+mod render;
+mod svg;
+#[cfg(test)]
+mod testutil;
+mod wall;
+mod zobrist;
+
+pub use config::{DrawRules, GameConfig, Side, MAX_PLAYERS, MAX_SIZE, MIN_SIZE};
+pub use error::IllegalAction;
+pub use outcome::{DrawReason, ForfeitReason, Outcome};
+pub use path::PathMode;
+pub use quoridor::{Command, Quoridor, Record, Undo};
+pub use render::{Charset, RenderOptions};
+pub use svg::SvgOptions;
+pub use wall::{Bitboard, Colour, Dir, WallTable, Walls};
+
+const DPOS: [(i8, i8); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+const DYDX2MOVEDIR: [[u8; 3]; 3] = [[3, 4, 5], [2, 8, 6], [1, 0, 7]];
@@ -0,0 +1,177 @@
+//! Line framing for the player protocol.
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::mem;
+
+/// Why a line was dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineError {
+    /// The line is longer than the limit; the rest of it up to the next LF was skipped.
+    TooLong,
+    InvalidUtf8,
+}
+
+/// Splits a byte stream into lines ending in LF or CRLF.
+pub struct LineReader<R> {
+    inner: BufReader<R>,
+    max_len: usize,
+    // 読みかけの行．読み取りがタイムアウトしても次の呼び出しで続きから読む
+    line: Vec<u8>,
+    too_long: bool,
+}
+
+impl<R: Read> LineReader<R> {
+    /// Lines longer than `max_len` bytes, not counting the line ending, are rejected.
+    pub fn new(inner: R, max_len: usize) -> Self {
+        LineReader {
+            inner: BufReader::new(inner),
+            max_len,
+            line: Vec::new(),
+            too_long: false,
+        }
+    }
+
+    /// Reads the next line without its line ending. Returns `None` at the end of the
+    /// stream; a last line without LF is still returned.
+    ///
+    /// If reading fails, e.g. with `WouldBlock` or `TimedOut`, the bytes read so far are
+    /// kept and the next call carries on with the same line.
+    pub fn next_line(&mut self) -> io::Result<Option<Result<String, LineError>>> {
+        loop {
+            let (done, used) = {
+                let buf = match self.inner.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                if buf.is_empty() {
+                    if self.line.is_empty() && !self.too_long {
+                        return Ok(None);
+                    }
+                    break;
+                }
+                let (done, used) = match buf.iter().position(|&b| b == b'\n') {
+                    Some(i) => (true, i + 1),
+                    None => (false, buf.len()),
+                };
+                // 長すぎる行は捨てながら LF まで読み進める
+                if !self.too_long {
+                    self.line.extend_from_slice(&buf[..used]);
+                    // 行末の CR LF の分だけ余裕を持たせる
+                    if self.line.len() > self.max_len + 2 {
+                        self.too_long = true;
+                        self.line.clear();
+                    }
+                }
+                (done, used)
+            };
+            self.inner.consume(used);
+            if done {
+                break;
+            }
+        }
+        let mut line = mem::take(&mut self.line);
+        if mem::replace(&mut self.too_long, false) {
+            return Ok(Some(Err(LineError::TooLong)));
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if line.len() > self.max_len {
+            return Ok(Some(Err(LineError::TooLong)));
+        }
+        Ok(Some(
+            String::from_utf8(line).map_err(|_| LineError::InvalidUtf8),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // 1回の read で1つずつ返す．空の塊は WouldBlock として返す
+    struct Chunks(VecDeque<&'static [u8]>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                None => Ok(0),
+                Some([]) => Err(io::ErrorKind::WouldBlock.into()),
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    Ok(chunk.len())
+                }
+            }
+        }
+    }
+
+    fn reader(chunks: &[&'static [u8]], max_len: usize) -> LineReader<Chunks> {
+        LineReader::new(Chunks(chunks.iter().cloned().collect()), max_len)
+    }
+
+    fn lines(chunks: &[&'static [u8]], max_len: usize) -> Vec<Result<String, LineError>> {
+        let mut r = reader(chunks, max_len);
+        let mut result = Vec::new();
+        while let Some(line) = r.next_line().unwrap() {
+            result.push(line);
+        }
+        result
+    }
+
+    fn ok(line: &str) -> Result<String, LineError> {
+        Ok(line.to_string())
+    }
+
+    #[test]
+    fn framing() {
+        // 1回で2行
+        assert_eq!(lines(&[b"e2\ne8\n"], 16), [ok("e2"), ok("e8")]);
+        // 2回に分かれた行
+        assert_eq!(lines(&[b"d7", b"h\n"], 16), [ok("d7h")]);
+        // LF と CRLF
+        assert_eq!(lines(&[b"e2\r\ne8\n\n"], 16), [ok("e2"), ok("e8"), ok("")]);
+        // LF のない最後の行
+        assert_eq!(lines(&[b"e2\ne8"], 16), [ok("e2"), ok("e8")]);
+        assert_eq!(lines(&[], 16), []);
+    }
+
+    #[test]
+    fn max_len() {
+        assert_eq!(lines(&[b"abcd\r\n"], 4), [ok("abcd")]);
+        assert_eq!(lines(&[b"abcde\n"], 4), [Err(LineError::TooLong)]);
+        assert_eq!(lines(&[b"abcde"], 4), [Err(LineError::TooLong)]);
+        // 長すぎる行を読み飛ばして次の行を読む
+        assert_eq!(
+            lines(&[b"abcdefgh", b"ijkl", b"mn\ne2\n"], 4),
+            [Err(LineError::TooLong), ok("e2")]
+        );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        assert_eq!(
+            lines(&[b"\xff\xfe\ne2\n"], 16),
+            [Err(LineError::InvalidUtf8), ok("e2")]
+        );
+    }
+
+    #[test]
+    fn resumes_after_would_block() {
+        let mut r = reader(&[b"HEL", b"", b"LO bob\n"], 16);
+        let e = r.next_line().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(r.next_line().unwrap(), Some(ok("HELLO bob")));
+        assert_eq!(r.next_line().unwrap(), None);
+
+        // 長すぎる行の読み飛ばしも続けられる
+        let mut r = reader(&[b"abcdefgh", b"", b"ij\ne2\n"], 4);
+        assert!(r.next_line().is_err());
+        assert_eq!(r.next_line().unwrap(), Some(Err(LineError::TooLong)));
+        assert_eq!(r.next_line().unwrap(), Some(ok("e2")));
+    }
+}
@@ -1,597 +1,175 @@
-extern crate bit_vec;
+extern crate quoridor_judge;
 extern crate uuid;
 extern crate ws;
 
-mod base64;
+mod hello;
+mod json;
+mod line;
+mod protocol;
 mod websocket;
 
-use uuid::Uuid;
-use bit_vec::BitVec;
-use base64::{append, bitvec_to_base64, from_u16, from_u8};
-use ws::{CloseCode, Factory, Handler, Message, Sender};
-use std::net::{TcpListener, TcpStream};
-use std::thread;
-use std::io::{Read, Write};
-use std::io;
+use hello::Hello;
+use json::Json;
+use line::{LineError, LineReader};
+use protocol::ProtocolError;
+use quoridor_judge::{
+    Charset, Colour, Command, ForfeitReason, GameConfig, IllegalAction, Outcome, Quoridor,
+    RenderOptions, SvgOptions,
+};
 use std::env;
 use std::fs;
-use std::time::Duration;
+use std::io;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process;
 use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use uuid::Uuid;
+use ws::Sender;
 
-const WALL_LIMIT: usize = 10;
-const H: usize = 9;
-const W: usize = 9;
-const PLAYER_NUM: usize = 2;
-const DPOS: [(i8, i8); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
-const DYDX2MOVEDIR: [[u8; 3]; 3] = [[3, 4, 5], [2, 8, 6], [1, 0, 7]];
-
-fn pos_to_u8((y, x): (usize, usize)) -> u8 {
-    ((W - 1 - y) * W + x) as u8
-}
-fn wall_to_u8((y, x): (usize, usize)) -> u8 {
-    ((W - 2 - y) * (W - 1) + x) as u8
-}
-
-fn in_area(y: usize, x: usize) -> bool {
-    y < H && x < W
-}
-fn in_wall_area(y: i8, x: i8) -> bool {
-    0 <= y && y < (H - 1) as i8 && 0 <= x && x < (W - 1) as i8
-}
-
-#[derive(Clone, Copy, PartialEq)]
-enum Colour {
-    White,
-    Black,
-}
+// プレイヤーから受け付ける1行の最大バイト数
+const MAX_LINE_LEN: usize = 1024;
+// 接続してから HELLO を待つ時間
+const HELLO_TIMEOUT: Duration = Duration::from_millis(500);
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Dir {
-    Vertical,
-    Horizontal,
+struct Player {
+    ip: String,
+    // HELLO で名乗った名前．名乗らなければ空
+    name: String,
+    // 取り決めたプロトコルのバージョン
+    version: u32,
 }
 
-impl Dir {
-    fn parse(input: &String) -> Option<Self> {
-        match input.as_ref() {
-            "V" => Some(Dir::Vertical),
-            "H" => Some(Dir::Horizontal),
-            _ => None,
+impl Player {
+    // 観戦画面やログに出す名前
+    fn label(&self) -> &str {
+        if self.name.is_empty() {
+            &self.ip
+        } else {
+            &self.name
         }
     }
 }
 
-type Table = Vec<Vec<Option<(Dir, Colour)>>>;
-
-#[derive(Clone)]
-struct WallTable {
-    data: Table,
+// 読み取りスレッドからメインループに渡すもの
+enum Input {
+    Line(String),
+    Invalid(ProtocolError),
+    // 接続が切れた
+    Closed,
 }
 
-impl WallTable {
-    fn get(&self, y: i8, x: i8) -> Option<(Dir, Colour)> {
-        if in_wall_area(y, x) {
-            self.data[y as usize][x as usize]
-        } else {
-            None
-        }
-    }
-    fn erase(&mut self, y: i8, x: i8) {
-        self.data[y as usize][x as usize] = None;
-    }
-    fn set(&mut self, y: i8, x: i8, dir: Dir, c: Colour) {
-        self.data[y as usize][x as usize] = Some((dir, c));
-    }
-    fn new() -> Self {
-        WallTable {
-            data: vec![vec![None; W - 1]; H - 1],
-        }
-    }
-
-    // 壁のみを考慮した次の手の方向を生成
-    fn next_wallmoves(&self, y: i8, x: i8) -> Vec<(i8, i8)> {
-        let mut wallmoves = Vec::new();
-        for (dy, dx) in DPOS.iter() {
-            if self.exist_wall(y, x, *dy, *dx) {
-                continue;
-            }
-            let (y, x) = (y + *dy, x + *dx);
-
-            if in_area(y as usize, x as usize) {
-                wallmoves.push((*dy, *dx));
-            }
-        }
-        wallmoves
-    }
-
-    fn checkwalldir(&self, y: i8, x: i8, dir: Dir) -> bool {
-        if let Some((d, _)) = self.get(y, x) {
-            d == dir
-        } else {
-            false
-        }
-    }
-
-    fn exist_wall(&self, y: i8, x: i8, dy: i8, dx: i8) -> bool {
-        let (y1, x1, y2, x2, dir) = if dx != 0 {
-            if dx == 1 {
-                (y - 1, x, y, x, Dir::Vertical)
-            } else {
-                (y - 1, x - 1, y, x - 1, Dir::Vertical)
-            }
-        } else {
-            if dy == 1 {
-                (y, x, y, x - 1, Dir::Horizontal)
-            } else {
-                (y - 1, x, y - 1, x - 1, Dir::Horizontal)
-            }
-        };
-        self.checkwalldir(y1, x1, dir) || self.checkwalldir(y2, x2, dir)
-    }
-
-    fn dfs(&self, y: i8, x: i8, gy: i8, visited: &mut Vec<Vec<bool>>) -> bool {
-        if y == gy {
-            return true;
-        }
-        visited[y as usize][x as usize] = true;
-        let moves = self.next_wallmoves(y, x);
-        for (dy, dx) in moves {
-            let (ny, nx) = (y + dy, x + dx);
-            if !visited[ny as usize][nx as usize] {
-                if self.dfs(ny, nx, gy, visited) {
-                    return true;
-                }
-            }
-        }
-        false
-    }
-    fn reachable(&self, y: i8, x: i8, gy: i8) -> bool {
-        let mut visited = vec![vec![false; W]; H];
-        self.dfs(y, x, gy, &mut visited)
+// 読んだ行をメインループに渡す形にする．空行は None
+fn to_input(line: Result<String, LineError>) -> Option<Input> {
+    match line {
+        Ok(ref text) if text.is_empty() => None,
+        Ok(text) => Some(Input::Line(text)),
+        Err(LineError::TooLong) => Some(Input::Invalid(ProtocolError::LineTooLong)),
+        Err(LineError::InvalidUtf8) => Some(Input::Invalid(ProtocolError::InvalidUtf8)),
     }
 }
 
-struct Quoridor {
-    table: WallTable,
-    white: (usize, usize),
-    black: (usize, usize),
-    is_white_turn: bool,
-    last_move: Option<(usize, usize)>,
-    turn_num: u16,
-    white_wall_num: usize,
-    black_wall_num: usize,
-    record: Vec<Record>,
+// 対局終了時に書き出すファイル
+#[derive(Clone, Copy, PartialEq)]
+enum RecordFormat {
+    History,
+    Json,
+    Both,
 }
 
-impl Quoridor {
-    fn new() -> Self {
-        Quoridor {
-            table: WallTable::new(),
-            white: (H - 1, W / 2),
-            black: (0, W / 2),
-            is_white_turn: true,
-            last_move: None,
-            turn_num: 1,
-            white_wall_num: WALL_LIMIT,
-            black_wall_num: WALL_LIMIT,
-            record: Vec::new(),
-        }
-    }
-
-    fn undo(&mut self) -> bool {
-        if self.record.len() < 1 {
-            return false;
-        }
-        let r = self.record.pop().unwrap();
-        match r {
-            Record::Piece(d) => {
-                let (dy, dx) = Record::to_dydx(d).expect(&format!("illegal record:{}", d));
-                if self.is_white_turn {
-                    self.black.0 = (self.black.0 as i8 - dy) as usize;
-                    self.black.1 = (self.black.1 as i8 - dx) as usize;
-                } else {
-                    self.white.0 = (self.white.0 as i8 - dy) as usize;
-                    self.white.1 = (self.white.1 as i8 - dx) as usize;
-                }
-            }
-            Record::Wall(y, x, _) => {
-                self.table.erase(y as i8, x as i8);
-                if self.is_white_turn {
-                    self.black_wall_num += 1;
-                } else {
-                    self.white_wall_num += 1;
-                }
-            }
-        }
-        self.turn_num -= 1;
-        self.last_move = match self.record.last() {
-            Some(Record::Wall(y, x, _)) => Some((*y, *x)),
-            Some(Record::Piece(_)) | None => None,
-        };
-        self.is_white_turn = !self.is_white_turn;
-        true
-    }
-
-    fn is_over(&self) -> Option<usize> {
-        if self.white.0 == 0 {
-            Some(0)
-        } else if self.black.0 == H - 1 {
-            Some(1)
-        } else {
-            None
-        }
-    }
-
-    fn settable(&self, y: usize, x: usize, dir: Dir) -> Result<(), String> {
-        if (self.is_white_turn && self.white_wall_num == 0)
-            || (!self.is_white_turn && self.black_wall_num == 0)
-        {
-            return Err("You have no wall".to_string());
-        }
-        let (y, x) = (y as i8 - 1, x as i8);
-        if !in_wall_area(y, x) {
-            return Err("Put position is out of bounds".to_string());
-        }
-        if self.table.get(y, x) != None {
-            return Err("Wall has already built".to_string());
-        }
-        match dir {
-            Dir::Horizontal => {
-                if self.table.checkwalldir(y, x - 1, Dir::Horizontal)
-                    || self.table.checkwalldir(y, x + 1, Dir::Horizontal)
-                {
-                    return Err("Wall has already built".to_string());
-                }
-            }
-            Dir::Vertical => {
-                if self.table.checkwalldir(y - 1, x, Dir::Vertical)
-                    || self.table.checkwalldir(y + 1, x, Dir::Vertical)
-                {
-                    return Err("Wall has already built".to_string());
-                }
-            }
-        }
-
-        let mut new_table = self.table.clone();
-        if self.is_white_turn {
-            new_table.set(y, x, dir, Colour::White);
-        } else {
-            new_table.set(y, x, dir, Colour::Black);
-        };
-        if new_table.reachable(self.white.0 as i8, self.white.1 as i8, 0)
-            && new_table.reachable(self.black.0 as i8, self.black.1 as i8, (H - 1) as i8)
-        {
-            Ok(())
-        } else {
-            Err("Unreachable".to_string())
-        }
-    }
-    fn next_moves(&self) -> Vec<(usize, usize)> {
-        let mut moves = Vec::new();
-        let (me, op) = if self.is_white_turn {
-            (
-                (self.white.0 as i8, self.white.1 as i8),
-                (self.black.0 as i8, self.black.1 as i8),
-            )
-        } else {
-            (
-                (self.black.0 as i8, self.black.1 as i8),
-                (self.white.0 as i8, self.white.1 as i8),
-            )
-        };
-        let wallmoves = self.table.next_wallmoves(me.0, me.1);
-        for (dy, dx) in wallmoves {
-            let (y, x) = (dy + me.0, dx + me.1);
-            if (y, x) == op {
-                if !in_area((y + dy) as usize, (x + dx) as usize)
-                    || self.table.exist_wall(y, x, dy, dx)
-                {
-                    for (dy, dx) in DPOS.iter() {
-                        if self.table.exist_wall(y, x, *dy, *dx) {
-                            continue;
-                        }
-                        let (y2, x2) = (y + dy, x + dx);
-                        if me == (y2, x2) {
-                            continue;
-                        }
-                        if in_area(y2 as usize, x2 as usize) {
-                            moves.push((y2 as usize, x2 as usize));
-                        }
-                    }
-                } else {
-                    let (y2, x2) = (y + dy, x + dx);
-                    if in_area(y2 as usize, x2 as usize) {
-                        moves.push((y2 as usize, x2 as usize));
-                    }
-                }
-            } else {
-                moves.push((y as usize, x as usize));
-            }
-        }
-
-        moves
-    }
-    fn movable(&self, y: usize, x: usize) -> Result<(), String> {
-        if !in_area(y, x) {
-            return Err("Position is out of bounds".to_string());
-        }
-        let moves = self.next_moves();
-        println!("{:?}", moves);
-        for m in moves {
-            if (y, x) == m {
-                return Ok(());
-            }
-        }
-        Err("illegal move".to_string())
-    }
-    fn display(&self) -> String {
-        let mut table: Vec<Vec<char>> = vec![vec![' '; 2 * W - 1]; 2 * H - 1];
-
-        for i in 0..H - 1 {
-            for j in 0..W - 1 {
-                match self.table.data[i][j] {
-                    Some((Dir::Vertical, _)) => {
-                        table[2 * i][2 * j + 1] = '|';
-                        table[2 * (i + 1)][2 * j + 1] = '|';
-                    }
-                    Some((Dir::Horizontal, _)) => {
-                        table[2 * i + 1][2 * j] = '-';
-                        table[2 * i + 1][2 * (j + 1)] = '-';
-                    }
-                    _ => {}
-                }
-            }
-        }
-        for i in 0..H - 1 {
-            for j in 0..W - 1 {
-                table[2 * i + 1][2 * j + 1] = '*';
-            }
-        }
-        table[2 * self.white.0][2 * self.white.1] = 'W';
-        table[2 * self.black.0][2 * self.black.1] = 'B';
-        let mut s = String::new();
-        s += &(0..(2 * W + 1)).map(|_| "#").collect::<String>();
-        s += "\n";
-        for i in 0..2 * H - 1 {
-            let row: String = table[i].clone().into_iter().collect();
-            s += &format!("#{}#\n", row);
-        }
-        s += &(0..(2 * W + 1)).map(|_| "#").collect::<String>();
-        s += "\n";
-        s
-    }
-
-    fn play(&mut self, com: &Command) -> Result<(), String> {
-        match com {
-            Command::Put(y, x, dir) => match self.settable(*y + 1, *x, *dir) {
-                Ok(()) => {
-                    if self.is_white_turn {
-                        self.table.set(*y as i8, *x as i8, *dir, Colour::White);
-                        self.white_wall_num -= 1;
-                    } else {
-                        self.table.set(*y as i8, *x as i8, *dir, Colour::Black);
-                        self.black_wall_num -= 1;
-                    }
-                    self.record.push(Record::Wall(*y, *x, *dir));
-                    self.last_move = Some((*y, *x));
-                }
-                Err(e) => {
-                    return Err(e);
-                }
-            },
-            Command::Move(y, x) => match self.movable(*y, *x) {
-                Ok(()) => {
-                    let (mut dy, mut dx) = if self.is_white_turn {
-                        let (dy, dx) =
-                            (self.white.0 as i8 - *y as i8, self.white.1 as i8 - *x as i8);
-                        self.white = (*y, *x);
-                        (dy, dx)
-                    } else {
-                        let (dy, dx) =
-                            (self.black.0 as i8 - *y as i8, self.black.1 as i8 - *x as i8);
-                        self.black = (*y, *x);
-                        (dy, dx)
-                    };
-                    if dy.abs() == 2 {
-                        dy /= 2;
-                    }
-                    if dx.abs() == 2 {
-                        dx /= 2;
-                    }
-                    let movedir = DYDX2MOVEDIR[(dy + 1) as usize][(dx + 1) as usize];
-                    println!("movedir:{}", movedir);
-                    self.record.push(Record::Piece(movedir));
-                    self.last_move = None;
-                }
-                Err(e) => {
-                    return Err(e);
-                }
-            },
+impl RecordFormat {
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "history" => Some(RecordFormat::History),
+            "json" => Some(RecordFormat::Json),
+            "both" => Some(RecordFormat::Both),
+            _ => None,
         }
-        self.is_white_turn = !self.is_white_turn;
-        self.turn_num += 1;
-        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Command {
-    Move(usize, usize),
-    Put(usize, usize, Dir),
+// 手番でないプレイヤーから手が届いたときの扱い
+#[derive(Clone, Copy, PartialEq)]
+enum TurnPolicy {
+    // 送り主にエラーを返して捨てる
+    Ignore,
+    // さらに観戦者にも知らせる
+    Warn,
+    // 送り主の反則負けにする
+    Forfeit,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Record {
-    Piece(u8),
-    Wall(usize, usize, Dir),
+impl TurnPolicy {
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "ignore" => Some(TurnPolicy::Ignore),
+            "warn" => Some(TurnPolicy::Warn),
+            "forfeit" => Some(TurnPolicy::Forfeit),
+            _ => None,
+        }
+    }
 }
 
-impl Record {
-    fn to_dydx(d: u8) -> Option<(i8, i8)> {
-        let dy = match d {
-            0 | 1 | 7 => -1,
-            2 | 6 => 0,
-            3 | 4 | 5 => 1,
-
-            _ => {
-                return None;
-            }
-        };
-        let dx = match d {
-            1 | 2 | 3 => 1,
-            0 | 4 => 0,
-            5 | 6 | 7 => -1,
-            _ => {
-                return None;
-            }
-        };
-        Some((dy, dx))
-    }
+// 読めない手や不正な手を送ったときの扱い
+#[derive(Clone, Copy, PartialEq)]
+enum InvalidPolicy {
+    // 1手番に N 回まで打ち直せる(None なら何度でも)
+    Retry(Option<usize>),
+    // すぐに反則負けにする
+    Forfeit,
+    // 打ち直せるが，1回ごとに持ち時間から差し引く
+    Penalty(Duration),
 }
 
-impl Command {
+impl InvalidPolicy {
     fn parse(input: &str) -> Option<Self> {
-        let input_vec: Vec<&str> = input.trim().split_whitespace().collect();
-        if input_vec.len() < 2 {
-            return None;
-        }
-        let x = input_vec[0].parse::<usize>().unwrap();
-        let y = input_vec[1].parse::<usize>().unwrap();
-        if input_vec.len() < 3 {
-            Some(Command::Move(y, x))
-        } else {
-            let dir = match Dir::parse(&input_vec[2].to_string()) {
-                Some(dir) => dir,
-                None => {
-                    return None;
-                }
-            };
-            Some(Command::Put(y, x, dir))
+        let mut parts = input.splitn(2, ':');
+        match (parts.next()?, parts.next()) {
+            ("retry", None) => Some(InvalidPolicy::Retry(None)),
+            ("retry", Some(n)) => n.parse().ok().map(|n| InvalidPolicy::Retry(Some(n))),
+            ("forfeit", None) => Some(InvalidPolicy::Forfeit),
+            ("penalty", Some(secs)) => secs
+                .parse()
+                .ok()
+                .map(|secs| InvalidPolicy::Penalty(Duration::from_secs(secs))),
+            _ => None,
         }
     }
 }
 
-struct Player {
-    ip: String,
-    name: String,
-}
-
 struct JudgeServer {
     ip: String,
     streams: Vec<TcpStream>,
     players: Vec<Player>,
     game: Quoridor,
     broadcaster: Sender,
+    // 引き分けを申し出たプレイヤー
+    draw_offers: Vec<bool>,
+    // 待ったを申し出たプレイヤー
+    undo_offers: Vec<bool>,
+    record_format: RecordFormat,
+    turn_policy: TurnPolicy,
+    invalid_policy: InvalidPolicy,
+    // 1局を通した各プレイヤーの持ち時間
+    time_limit: Option<Duration>,
+    // 今の手番で送られた読めない手や不正な手の数
+    invalid_count: usize,
+    // 今の手番で課されたペナルティ
+    turn_penalty: Duration,
+    // 各プレイヤーが課されたペナルティの合計
+    penalties: Vec<Duration>,
+    start_time: SystemTime,
+    // 手番が回ってきた時刻
+    turn_start: Instant,
+    // 各手の考慮時間
+    think_times: Vec<Duration>,
+    // 各プレイヤーが使った時間の合計．待ったで手を戻しても減らさない
+    used: Vec<Duration>,
 }
 
 impl JudgeServer {
-    // README.md
-    fn socketformat(&self) -> String {
-        let mut output = String::new();
-        output += &format!(
-            "{} {} {} {} {} {}\n",
-            self.game.white.1,
-            self.game.white.0,
-            self.game.black.1,
-            self.game.black.0,
-            self.game.white_wall_num,
-            self.game.black_wall_num
-        );
-
-        for rows in &self.game.table.data {
-            for cell in rows {
-                output += &format!(
-                    "{} ",
-                    match cell {
-                        Some((Dir::Horizontal, _)) => 1,
-                        Some((Dir::Vertical, _)) => 2,
-                        None => 0,
-                    }
-                );
-            }
-            output += "\n";
-        }
-        output
-    }
-
-    // https://www.quoridorfansite.com/tools/qfb.html
-    fn viewformat(&self) -> String {
-        let mut bv = BitVec::new();
-        bv.push(true);
-        bv.push(false);
-        append(&mut bv, from_u8(pos_to_u8(self.game.white), 7));
-        append(&mut bv, from_u8(pos_to_u8(self.game.black), 7));
-        let mut white_h_walls = Vec::new();
-        let mut white_v_walls = Vec::new();
-        let mut black_h_walls = Vec::new();
-        let mut black_v_walls = Vec::new();
-        for (y, row) in self.game.table.data.iter().enumerate() {
-            for (x, wall) in row.iter().enumerate() {
-                match wall {
-                    Some((Dir::Horizontal, Colour::White)) => {
-                        white_h_walls.push((y, x));
-                    }
-                    Some((Dir::Vertical, Colour::White)) => {
-                        white_v_walls.push((y, x));
-                    }
-                    Some((Dir::Horizontal, Colour::Black)) => {
-                        black_h_walls.push((y, x));
-                    }
-                    Some((Dir::Vertical, Colour::Black)) => {
-                        black_v_walls.push((y, x));
-                    }
-                    None => {}
-                }
-            }
-        }
-        append(&mut bv, from_u8(white_h_walls.len() as u8, 4));
-        for pos in white_h_walls {
-            append(&mut bv, from_u8(wall_to_u8(pos), 6));
-        }
-        append(&mut bv, from_u8(white_v_walls.len() as u8, 4));
-        for pos in white_v_walls {
-            append(&mut bv, from_u8(wall_to_u8(pos), 6));
-        }
-        append(&mut bv, from_u8(black_h_walls.len() as u8, 4));
-        for pos in black_h_walls {
-            append(&mut bv, from_u8(wall_to_u8(pos), 6));
-        }
-        append(&mut bv, from_u8(black_v_walls.len() as u8, 4));
-        for pos in black_v_walls {
-            append(&mut bv, from_u8(wall_to_u8(pos), 6));
-        }
-        bv.push(self.game.is_white_turn);
-        if let Some((y, x)) = self.game.last_move {
-            bv.push(true);
-            append(&mut bv, from_u8(wall_to_u8((y, x)), 6));
-        } else {
-            bv.push(false);
-        }
-        append(&mut bv, from_u16(self.game.turn_num, 10));
-        bitvec_to_base64(bv)
-    }
-
-    fn historyformat(&self) -> String {
-        let mut bv = BitVec::new();
-        bv.push(false);
-        bv.push(true);
-        append(&mut bv, from_u16(self.game.record.len() as u16, 10));
-        for com in &self.game.record {
-            match com {
-                Record::Piece(movedir) => {
-                    bv.push(false);
-                    append(&mut bv, from_u8(*movedir, 3));
-                }
-                Record::Wall(y, x, dir) => {
-                    bv.push(true);
-                    bv.push(*dir == Dir::Vertical);
-                    append(&mut bv, from_u8(wall_to_u8((*y, *x)), 6));
-                }
-            }
-        }
-        bitvec_to_base64(bv)
-    }
-
     fn start(&mut self) -> io::Result<()> {
         println!("listening {}", self.ip);
         println!("{}", self.game.display());
@@ -600,139 +178,833 @@ impl JudgeServer {
         let mut num = 0;
         let (tx, rx) = mpsc::channel();
 
-        while num < PLAYER_NUM {
-            let (mut stream, addr) = match lis.accept() {
+        while num < self.game.config().player_num {
+            let (stream, addr) = match lis.accept() {
                 Ok(result) => result,
                 Err(e) => {
                     println!("couldn't get client: {:?}", e);
                     break;
                 }
             };
-            self.streams.push(stream.try_clone().unwrap());
-            self.players.push(Player {
+            let mut player = Player {
                 ip: addr.to_string(),
                 name: String::new(),
-            });
+                version: 1,
+            };
+            let mut lines = LineReader::new(stream.try_clone().unwrap(), MAX_LINE_LEN);
+            let first = Self::handshake(
+                num,
+                &stream,
+                &mut lines,
+                &mut player,
+                self.game.config(),
+                self.time_limit,
+            );
+            // 挨拶の途中で切れたら席に着かせない
+            let first = match first {
+                Ok(first) => first,
+                Err(e) => {
+                    println!("{} left: {}", addr, e);
+                    continue;
+                }
+            };
+            self.streams.push(stream);
+            if player.name.is_empty() {
+                println!("{} came", addr);
+            } else {
+                println!("{} ({}) came", player.name, addr);
+            }
             self.broadcaster
-                .send(ws::Message::Text(format!("mesg:Player {} came", addr)))
+                .send(ws::Message::Text(format!(
+                    "mesg:Player {} came",
+                    player.label()
+                )))
                 .unwrap();
+            self.players.push(player);
+            self.draw_offers.push(false);
+            self.undo_offers.push(false);
+            self.penalties.push(Duration::from_secs(0));
+            self.used.push(Duration::from_secs(0));
 
             let tx = tx.clone();
 
-            let _ = thread::spawn(move || -> io::Result<()> {
+            let _ = thread::spawn(move || {
                 let id = num;
-                println!("{} came", addr);
-                loop {
-                    let mut b = [0; 128];
-                    let n = stream.read(&mut b)?;
-                    if n == 0 {
-                        return Ok(());
-                    } else {
-                        let message: Vec<u8> = b.iter()
-                            .take_while(|&c| *c != 13 && *c != 0)
-                            .map(|c| *c)
-                            .collect();
-
-                        let message = String::from_utf8(message).unwrap();
-                        let _ = tx.send((id, message));
+                if let Some(input) = first.and_then(to_input) {
+                    let _ = tx.send((id, input));
+                }
+                while let Ok(Some(line)) = lines.next_line() {
+                    if let Some(input) = to_input(line) {
+                        let _ = tx.send((id, input));
                     }
                 }
+                let _ = tx.send((id, Input::Closed));
             });
             num += 1;
         }
 
         println!("ready");
         self.broadcaster
-            .send(ws::Message::Text(format!("mesg:Game Start")))
+            .send(ws::Message::Text("mesg:Game Start".to_string()))
             .unwrap();
 
-        for (id, mut stream) in self.streams.iter().enumerate() {
-            stream.write(&format!("{}\n", id).as_bytes())?;
+        self.start_time = SystemTime::now();
+        for id in 0..self.streams.len() {
+            if self.send(id, &format!("{}\n", id)) {
+                return Ok(());
+            }
         }
-
-        let socketmsg = self.socketformat();
-        self.streams[0].write(&socketmsg.as_bytes())?;
+        if self.send_state() {
+            return Ok(());
+        }
+        self.turn_start = Instant::now();
         loop {
             thread::sleep(Duration::from_micros(100));
-            for (from_id, message) in rx.recv().iter() {
-                println!("{:?}", message);
+            let received = match self.time_left() {
+                Some(left) => rx.recv_timeout(left).ok(),
+                None => rx.recv().ok(),
+            };
+            // 持ち時間を使い切ったら，遅れて届いた手も受け付けない
+            if self.time_left() == Some(Duration::from_secs(0)) {
+                let turn = self.game.turn();
+                self.game.forfeit(turn, ForfeitReason::Timeout);
+                self.end_if_over();
+                return Ok(());
+            }
+            for (from_id, message) in received.iter() {
+                let message = match message {
+                    Input::Line(message) => message,
+                    Input::Invalid(e) => {
+                        if self.invalid_move(*from_id, *e) {
+                            return Ok(());
+                        }
+                        continue;
+                    }
+                    Input::Closed => {
+                        self.disconnected(*from_id);
+                        return Ok(());
+                    }
+                };
+                println!("{}: {:?}", self.players[*from_id].label(), message);
+                // 待ち時間を過ぎて届いた挨拶は手として数えない
+                if Hello::parse(message).is_some() {
+                    if self.send_error(*from_id, ProtocolError::LateHello) {
+                        return Ok(());
+                    }
+                    continue;
+                }
                 let s: Vec<&str> = message.split(":").collect();
                 if s.len() == 2 {
                     match s[1] {
+                        "draw" => {
+                            if !self.game.config().draw.agreement {
+                                if self.send_error(*from_id, IllegalAction::DrawNotAllowed.into()) {
+                                    return Ok(());
+                                }
+                                continue;
+                            }
+                            self.draw_offers[*from_id] = true;
+                            self.broadcaster
+                                .send(ws::Message::Text(format!(
+                                    "mesg:{} offers a draw",
+                                    self.players[*from_id].label()
+                                )))
+                                .unwrap();
+                            if self.draw_offers.iter().all(|&offered| offered) {
+                                self.game.agree_draw().unwrap();
+                                if let Some(outcome) = self.game.is_over() {
+                                    self.finish(outcome);
+                                    return Ok(());
+                                }
+                            }
+                            continue;
+                        }
                         "undo" => {
-                            self.game.undo();
-                            self.game.undo();
-                            let sendmsg = self.viewformat();
+                            // 全員が申し出たときだけ戻す
+                            self.undo_offers[*from_id] = true;
                             self.broadcaster
-                                .send(ws::Message::Text(format!("mesg:undo")))
+                                .send(ws::Message::Text(format!(
+                                    "mesg:{} asks to undo",
+                                    self.players[*from_id].label()
+                                )))
                                 .unwrap();
-
+                            if !self.undo_offers.iter().all(|&offered| offered) {
+                                continue;
+                            }
+                            self.undo_offers = vec![false; self.players.len()];
+                            self.draw_offers = vec![false; self.players.len()];
+                            let turn = self.game.turn();
+                            self.used[turn] += self.turn_start.elapsed() + self.turn_penalty;
+                            for _ in 0..2 {
+                                if self.game.undo() {
+                                    self.think_times.pop();
+                                }
+                            }
+                            self.turn_start = Instant::now();
+                            self.invalid_count = 0;
+                            self.turn_penalty = Duration::from_secs(0);
                             self.broadcaster
-                                .send(ws::Message::Text(format!("qfcode:{}", sendmsg)))
+                                .send(ws::Message::Text("mesg:undo".to_string()))
                                 .unwrap();
+
+                            if let Some(sendmsg) = self.game.viewformat() {
+                                self.broadcaster
+                                    .send(ws::Message::Text(format!("qfcode:{}", sendmsg)))
+                                    .unwrap();
+                            }
+                            if self.send_state() {
+                                return Ok(());
+                            }
                             continue;
                         }
                         other => {
                             self.broadcaster
                                 .send(ws::Message::Text(format!(
                                     "mesg:{}:{}",
-                                    self.players[*from_id].ip, other
+                                    self.players[*from_id].label(),
+                                    other
                                 )))
                                 .unwrap();
                             continue;
                         }
                     }
                 }
-                let command = Command::parse(&message).expect("parse error");
+                let command = match Command::parse_notation(self.game.config(), message)
+                    .or_else(|| Command::parse(message))
+                {
+                    Some(command) => command,
+                    None => {
+                        if self.invalid_move(*from_id, ProtocolError::Unparsable) {
+                            return Ok(());
+                        }
+                        continue;
+                    }
+                };
 
-                if let Err(e) = self.game.play(&command) {
-                    println!("{}", e);
-                    break;
+                if let Err(e) = self.game.play_as(*from_id, &command) {
+                    let ended = match e {
+                        IllegalAction::NotYourTurn(_) => self.out_of_turn(*from_id, e),
+                        _ => self.invalid_move(*from_id, e.into()),
+                    };
+                    if ended {
+                        return Ok(());
+                    }
+                    continue;
                 }
+                let think = self.turn_start.elapsed() + self.turn_penalty;
+                self.think_times.push(think);
+                self.used[*from_id] += think;
+                self.invalid_count = 0;
+                self.turn_penalty = Duration::from_secs(0);
+                self.draw_offers = vec![false; self.players.len()];
+                self.undo_offers = vec![false; self.players.len()];
 
                 let result = self.game.display();
-                let socketmsg = self.socketformat();
-                let sendmsg = self.viewformat();
+                let socketmsg = self.game.socketformat();
                 println!("{}", result);
                 println!("socket format:\n{}", socketmsg);
-                println!("browser format:\n{}", sendmsg);
-                self.broadcaster
-                    .send(ws::Message::Text(format!("qfcode:{}", sendmsg)))
-                    .unwrap();
-
-                {
-                    let mut stream: &TcpStream =
-                        &mut self.streams[(from_id + 1) as usize % PLAYER_NUM];
-                    stream.write(&socketmsg.as_bytes())?;
-                }
-                if let Some(winner) = self.game.is_over() {
-                    println!("Player {} win!", winner);
-                    let winner_name = if winner == 0 {
-                        "white".to_string()
-                    } else {
-                        "black".to_string()
-                    };
+                if let Some(sendmsg) = self.game.viewformat() {
+                    println!("browser format:\n{}", sendmsg);
                     self.broadcaster
-                        .send(ws::Message::Text(format!(
-                            "mesg:Player {} win!",
-                            winner_name
-                        )))
+                        .send(ws::Message::Text(format!("qfcode:{}", sendmsg)))
                         .unwrap();
-                    thread::sleep(Duration::from_micros(1000));
-                    // output history file
-                    let game_uuid = Uuid::new_v4();
-                    let filename = game_uuid.to_string();
+                }
 
-                    let mut f = fs::File::create(filename).unwrap();
-                    f.write_all(self.historyformat().as_bytes()).unwrap();
+                if self.send_state() {
                     return Ok(());
                 }
+                self.turn_start = Instant::now();
+                if let Some(outcome) = self.game.is_over() {
+                    self.finish(outcome);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // 接続直後の HELLO を待って答える．HELLO でない最初の行はそのまま返す
+    fn handshake(
+        seat: usize,
+        mut stream: &TcpStream,
+        lines: &mut LineReader<TcpStream>,
+        player: &mut Player,
+        config: &GameConfig,
+        time_limit: Option<Duration>,
+    ) -> io::Result<Option<Result<String, LineError>>> {
+        stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+        let first = match lines.next_line() {
+            Ok(None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "closed before the game started",
+                ))
             }
+            Ok(first) => first,
+            // 時間内に1行届かなければ従来のクライアントとして扱う．読みかけの行は続きから読む
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        stream.set_read_timeout(None)?;
+        let hello = match first {
+            Some(Ok(ref text)) => Hello::parse(text),
+            _ => None,
+        };
+        match hello {
+            Some(Ok(hello)) => {
+                player.name = hello.name.clone();
+                player.version = hello.negotiated();
+                stream.write_all(
+                    hello::welcome(player.version, seat, config, time_limit).as_bytes(),
+                )?;
+            }
+            Some(Err(e)) => {
+                stream.write_all(format!("error {} {}\n", e.code(), e).as_bytes())?;
+            }
+            None => return Ok(first),
+        }
+        Ok(None)
+    }
+
+    // 手番でない手を方針に従って扱う．対局が終わったら true
+    fn out_of_turn(&mut self, id: usize, e: IllegalAction) -> bool {
+        println!("{}", e);
+        if self.send_error(id, e.into()) {
+            return true;
+        }
+        match self.turn_policy {
+            TurnPolicy::Ignore => {}
+            TurnPolicy::Warn => {
+                self.broadcaster
+                    .send(ws::Message::Text(format!(
+                        "mesg:{} played out of turn",
+                        self.players[id].label()
+                    )))
+                    .unwrap();
+            }
+            TurnPolicy::Forfeit => {
+                self.game.forfeit(id, ForfeitReason::OutOfTurn);
+                return self.end_if_over();
+            }
+        }
+        false
+    }
+
+    // 読めない手や不正な手を方針に従って扱う．対局が終わったら true
+    fn invalid_move(&mut self, id: usize, e: ProtocolError) -> bool {
+        println!("{}", e);
+        if self.send_error(id, e) {
+            return true;
+        }
+        // 手番でないプレイヤーの読めない行はエラーを返すだけ
+        if id != self.game.turn() {
+            return false;
+        }
+        self.invalid_count += 1;
+        let forfeit = match self.invalid_policy {
+            InvalidPolicy::Retry(limit) => limit.is_some_and(|n| self.invalid_count > n),
+            InvalidPolicy::Forfeit => true,
+            InvalidPolicy::Penalty(penalty) => {
+                self.turn_penalty += penalty;
+                self.penalties[id] += penalty;
+                false
+            }
+        };
+        if forfeit {
+            self.game.forfeit(id, ForfeitReason::InvalidMoves);
+            return self.end_if_over();
+        }
+        false
+    }
+
+    fn end_if_over(&mut self) -> bool {
+        match self.game.is_over() {
+            Some(outcome) => {
+                self.finish(outcome);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 手番のプレイヤーに，取り決めたバージョンの形式で局面を送る．対局が終わったら true
+    fn send_state(&mut self) -> bool {
+        let seat = self.game.turn();
+        let msg = if self.players[seat].version >= 2 {
+            self.game.socketformat_v2(seat, &self.clocks())
+        } else {
+            self.game.socketformat()
+        };
+        self.send(seat, &msg)
+    }
+
+    // 書き込めなければ接続が切れたとみなす．それで対局が終わったら true
+    fn send(&mut self, id: usize, msg: &str) -> bool {
+        let mut stream: &TcpStream = &self.streams[id];
+        if stream.write_all(msg.as_bytes()).is_ok() {
+            return false;
+        }
+        // 決着のついた後の局面は届かなくてもよい
+        if self.game.is_over().is_some() {
+            return false;
+        }
+        self.disconnected(id);
+        true
+    }
+
+    // 接続の切れたプレイヤーの負けで対局を終える
+    fn disconnected(&mut self, id: usize) {
+        println!("{} disconnected", self.players[id].label());
+        self.game.forfeit(id, ForfeitReason::Disconnected);
+        self.end_if_over();
+    }
+
+    // 記録の最初の手を指したプレイヤー
+    fn first_seat(&self) -> usize {
+        let n = self.game.config().player_num;
+        (self.game.turn() + n - self.game.record().len() % n) % n
+    }
+
+    // 手番のプレイヤーの残り時間．持ち時間がなければ None
+    fn time_left(&self) -> Option<Duration> {
+        let limit = self.time_limit?;
+        let turn = self.game.turn();
+        let used = Duration::from_millis(self.clocks()[turn])
+            + self.turn_start.elapsed()
+            + self.turn_penalty;
+        Some(limit.saturating_sub(used))
+    }
+
+    // 各プレイヤーの考慮時間の合計(ミリ秒)
+    fn clocks(&self) -> Vec<u64> {
+        self.used.iter().map(|d| d.as_millis() as u64).collect()
+    }
+
+    // 不正な手を送ったプレイヤーにエラーコードと理由を返す．対局が終わったら true
+    fn send_error(&mut self, id: usize, e: ProtocolError) -> bool {
+        self.send(id, &format!("error {} {}\n", e.code(), e))
+    }
+
+    fn finish(&mut self, outcome: Outcome) {
+        println!("{}", outcome);
+        let mesg = match outcome {
+            Outcome::Win(_) => format!("mesg:Player {}!", outcome),
+            Outcome::Draw(_) => format!("mesg:Game ends in a {}", outcome),
+            Outcome::Forfeit(..) => format!("mesg:{}", outcome),
+        };
+        self.broadcaster.send(ws::Message::Text(mesg)).unwrap();
+        thread::sleep(Duration::from_micros(1000));
+        let end_time = SystemTime::now();
+        let filename = Uuid::new_v4().to_string();
+        // output history file
+        let history = match self.record_format {
+            RecordFormat::Json => None,
+            _ => self.game.historyformat(),
+        };
+        if let Some(ref history) = history {
+            let mut f = fs::File::create(&filename).unwrap();
+            f.write_all(format!("{}\n{}\n{}\n", history, outcome, self.game.notation()).as_bytes())
+                .unwrap();
+        }
+        // 棋譜のコードにできない対局は JSON だけでも残す
+        if self.record_format == RecordFormat::History && history.is_none() {
+            println!(
+                "cannot write the history file; writing {}.json instead",
+                filename
+            );
+        }
+        if self.record_format != RecordFormat::History || history.is_none() {
+            let record = self.json_record(outcome, end_time);
+            let mut f = fs::File::create(format!("{}.json", filename)).unwrap();
+            f.write_all(format!("{}\n", record).as_bytes()).unwrap();
+        }
+    }
+
+    fn json_record(&self, outcome: Outcome, end_time: SystemTime) -> Json {
+        let config = self.game.config();
+        let n = config.player_num;
+        let first = self.first_seat();
+        let actions = self
+            .game
+            .record()
+            .iter()
+            .zip(&self.think_times)
+            .enumerate()
+            .map(|(i, (r, think))| {
+                Json::Object(vec![
+                    ("ply", (i + 1).into()),
+                    (
+                        "player",
+                        Colour::from_index((first + i) % n).to_string().into(),
+                    ),
+                    ("command", r.command().to_string().into()),
+                    ("notation", r.command().to_notation(config).into()),
+                    ("think_ms", Json::Number(think.as_millis() as u64)),
+                ])
+            })
+            .collect();
+        let players = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(id, player)| {
+                Json::Object(vec![
+                    ("seat", id.into()),
+                    ("colour", Colour::from_index(id).to_string().into()),
+                    ("name", Json::str(&player.name)),
+                    ("address", Json::str(&player.ip)),
+                    ("protocol", (player.version as usize).into()),
+                    (
+                        "penalty_ms",
+                        Json::Number(self.penalties[id].as_millis() as u64),
+                    ),
+                ])
+            })
+            .collect();
+        let (winner, reason) = match outcome {
+            Outcome::Win(id) => (Some(Colour::from_index(id).to_string()), "goal".to_string()),
+            Outcome::Draw(reason) => (None, reason.to_string()),
+            // 2人対局なら相手の勝ち
+            Outcome::Forfeit(id, reason) if n == 2 => (
+                Some(Colour::from_index(1 - id).to_string()),
+                reason.to_string(),
+            ),
+            Outcome::Forfeit(_, reason) => (None, reason.to_string()),
+        };
+        Json::Object(vec![
+            (
+                "config",
+                Json::Object(vec![
+                    ("height", config.height.into()),
+                    ("width", config.width.into()),
+                    ("wall_limit", config.wall_limit.into()),
+                    ("player_num", n.into()),
+                    ("repetition", config.draw.repetition.into()),
+                    ("max_plies", config.draw.max_plies.into()),
+                    ("draw_offers", Json::Bool(config.draw.agreement)),
+                    (
+                        "time_limit",
+                        self.time_limit.map(|t| t.as_secs() as usize).into(),
+                    ),
+                ]),
+            ),
+            ("players", Json::Array(players)),
+            ("start_time", json::timestamp(self.start_time).into()),
+            ("end_time", json::timestamp(end_time).into()),
+            ("actions", Json::Array(actions)),
+            (
+                "result",
+                Json::Object(vec![
+                    ("winner", winner.into()),
+                    ("reason", reason.into()),
+                    ("text", outcome.to_string().into()),
+                ]),
+            ),
+            ("notation", self.game.notation().into()),
+            ("qfcode", self.game.viewformat().into()),
+            ("history", self.game.historyformat().into()),
+        ])
+    }
+}
+// `--name value` を取り除いて値を返す
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    if i + 1 >= args.len() {
+        eprintln!("{} needs a value", name);
+        process::exit(1);
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Some(value)
+}
+
+fn game_config(args: &mut Vec<String>) -> GameConfig {
+    let default = GameConfig::default();
+    let size = take_option(args, "--size")
+        .map(|s| s.parse::<usize>().expect("--size must be a number"))
+        .unwrap_or(default.height);
+    let players = take_option(args, "--players")
+        .map(|s| s.parse::<usize>().expect("--players must be a number"))
+        .unwrap_or(default.player_num);
+    let walls = take_option(args, "--walls")
+        .map(|s| s.parse::<usize>().expect("--walls must be a number"))
+        .unwrap_or_else(|| GameConfig::default_wall_limit(players));
+    let mut config = match GameConfig::new(size, walls, players) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    // 0 で千日手判定を無効にする
+    if let Some(n) = take_option(args, "--repetition") {
+        let n = n.parse::<usize>().expect("--repetition must be a number");
+        config.draw.repetition = if n == 0 { None } else { Some(n) };
+    }
+    if let Some(n) = take_option(args, "--max-plies") {
+        config.draw.max_plies = Some(n.parse::<usize>().expect("--max-plies must be a number"));
+    }
+    if take_flag(args, "--allow-draw-offers") {
+        config.draw.agreement = true;
+    }
+    config
+}
+
+// `--start-position <file>` があればその局面から始める
+fn start_position(args: &mut Vec<String>, config: GameConfig) -> Quoridor {
+    let turn = take_option(args, "--start-turn")
+        .map(|s| s.parse::<usize>().expect("--start-turn must be a number"))
+        .unwrap_or(0);
+    let path = match take_option(args, "--start-position") {
+        Some(path) => path,
+        None => return Quoridor::new(config),
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    match Quoridor::from_socketformat(config, &text, turn) {
+        Ok(game) => game,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
         }
     }
 }
+
+// `name` があれば取り除いて true を返す
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|a| a == name) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+// perft <depth> [--divide] [--moves "x y;x y H;..."]
+fn perft(mut args: Vec<String>, mut game: Quoridor) {
+    let moves = take_option(&mut args, "--moves");
+    let divide = take_flag(&mut args, "--divide");
+    let depth = match args.get(2).map(|s| s.parse::<usize>()) {
+        Some(Ok(depth)) => depth,
+        _ => {
+            eprintln!(
+                "usage: {} perft <depth> [--divide] [--moves \"x y;x y H;...\"]",
+                args[0]
+            );
+            process::exit(1);
+        }
+    };
+    for m in moves.iter().flat_map(|m| m.split(';')) {
+        let played = Command::parse(m)
+            .ok_or_else(|| "parse error".to_string())
+            .and_then(|com| game.play(&com).map_err(|e| e.to_string()));
+        if let Err(e) = played {
+            eprintln!("{}: {}", m.trim(), e);
+            process::exit(1);
+        }
+    }
+    let start = Instant::now();
+    let nodes = if divide {
+        let mut total = 0;
+        for (com, n) in game.divide(depth) {
+            println!("{}: {}", com, n);
+            total += n;
+        }
+        total
+    } else {
+        game.perft(depth)
+    };
+    println!("nodes {} ({:?})", nodes, start.elapsed());
+}
+
+// replay <file> [--ply N] [--qfcode] [--notation] [--unicode] [--colour]
+fn replay(mut args: Vec<String>, config: GameConfig) {
+    let target = take_option(&mut args, "--ply")
+        .map(|s| s.parse::<usize>().expect("--ply must be a number"));
+    let qfcode = take_flag(&mut args, "--qfcode");
+    let notation = take_flag(&mut args, "--notation");
+    let opts = RenderOptions {
+        charset: if take_flag(&mut args, "--unicode") {
+            Charset::Unicode
+        } else {
+            Charset::Ascii
+        },
+        ansi: take_flag(&mut args, "--colour"),
+        ..RenderOptions::default()
+    };
+    let path = match args.get(2) {
+        Some(path) => path.clone(),
+        None => {
+            eprintln!(
+                "usage: {} replay <file> [--ply N] [--qfcode] [--notation] [--unicode] [--colour]",
+                args[0]
+            );
+            process::exit(1);
+        }
+    };
+    let (history, result) = read_game(&path, config, notation);
+    check_ply(&history, target);
+
+    let mut game = Quoridor::new(config);
+    let show = |game: &Quoridor, ply: usize| {
+        if target.is_none_or(|t| t == ply) {
+            println!("{}", game.render(&opts));
+            if qfcode {
+                match game.viewformat() {
+                    Some(code) => println!("qfcode: {}", code),
+                    None => println!("qfcode: not available for this game"),
+                }
+            }
+        }
+    };
+    show(&game, 0);
+    for (i, r) in history.record().iter().enumerate() {
+        let ply = i + 1;
+        if target.is_none_or(|t| t == ply) {
+            println!(
+                "ply {}: {} ({}, {})",
+                ply,
+                r.command().to_notation(&config),
+                r.command(),
+                Colour::from_index(game.turn())
+            );
+        }
+        game.play(&r.command()).unwrap();
+        show(&game, ply);
+    }
+    if target.is_none() {
+        if let Some(result) = result {
+            println!("{}", result);
+        }
+    }
+}
+
+// svg <file> [--ply N] [--notation] [--out-dir DIR]
+fn svg(mut args: Vec<String>, config: GameConfig) {
+    let target = take_option(&mut args, "--ply")
+        .map(|s| s.parse::<usize>().expect("--ply must be a number"));
+    let notation = take_flag(&mut args, "--notation");
+    let out_dir = take_option(&mut args, "--out-dir").unwrap_or_else(|| ".".to_string());
+    let path = match args.get(2) {
+        Some(path) => path.clone(),
+        None => {
+            eprintln!(
+                "usage: {} svg <file> [--ply N] [--notation] [--out-dir DIR]",
+                args[0]
+            );
+            process::exit(1);
+        }
+    };
+    let (history, _) = read_game(&path, config, notation);
+    check_ply(&history, target);
+
+    let stem = Path::new(&path)
+        .file_stem()
+        .map_or("game".into(), |s| s.to_string_lossy());
+    let opts = SvgOptions::default();
+    let mut game = Quoridor::new(config);
+    for ply in 0..=history.record().len() {
+        if ply > 0 {
+            game.play(&history.record()[ply - 1].command()).unwrap();
+        }
+        if target.is_none_or(|t| t == ply) {
+            let file = Path::new(&out_dir).join(format!("{}-{:03}.svg", stem, ply));
+            if let Err(e) = fs::write(&file, game.svg(&opts)) {
+                eprintln!("{}: {}", file.display(), e);
+                process::exit(1);
+            }
+            println!("{}", file.display());
+        }
+    }
+}
+
+// 棋譜ファイルを読んで，対局と結果の行を返す
+fn read_game(path: &str, config: GameConfig, notation: bool) -> (Quoridor, Option<String>) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    // 1行目が棋譜，2行目があれば結果
+    let mut lines = text.lines();
+    let loaded = if notation {
+        Quoridor::from_notation(config, &text).map(|game| (game, None))
+    } else {
+        Quoridor::from_history(config, lines.next().unwrap_or(""))
+            .map(|game| (game, lines.next().map(|s| s.to_string())))
+    };
+    match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn check_ply(game: &Quoridor, target: Option<usize>) {
+    if let Some(ply) = target {
+        if ply > game.record().len() {
+            eprintln!("the game has only {} plies", game.record().len());
+            process::exit(1);
+        }
+    }
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let config = game_config(&mut args);
+    let record_format = match take_option(&mut args, "--record") {
+        Some(format) => RecordFormat::parse(&format).unwrap_or_else(|| {
+            eprintln!("--record must be history, json or both");
+            process::exit(1);
+        }),
+        None => RecordFormat::Both,
+    };
+    let turn_policy = match take_option(&mut args, "--turn-policy") {
+        Some(policy) => TurnPolicy::parse(&policy).unwrap_or_else(|| {
+            eprintln!("--turn-policy must be ignore, warn or forfeit");
+            process::exit(1);
+        }),
+        None => TurnPolicy::Warn,
+    };
+    let invalid_policy = match take_option(&mut args, "--invalid-policy") {
+        Some(policy) => InvalidPolicy::parse(&policy).unwrap_or_else(|| {
+            eprintln!("--invalid-policy must be retry, retry:N, forfeit or penalty:SECS");
+            process::exit(1);
+        }),
+        None => InvalidPolicy::Retry(None),
+    };
+    let time_limit = take_option(&mut args, "--time-limit")
+        .map(|s| Duration::from_secs(s.parse::<u64>().expect("--time-limit must be a number")));
+    if let (InvalidPolicy::Penalty(_), None) = (invalid_policy, time_limit) {
+        eprintln!("--invalid-policy penalty:SECS needs --time-limit");
+        process::exit(1);
+    }
+    let game = start_position(&mut args, config);
+    if args.get(1).map(|s| s.as_str()) == Some("perft") {
+        perft(args, game);
+        return;
+    }
+    if args.get(1).map(|s| s.as_str()) == Some("replay") {
+        replay(args, config);
+        return;
+    }
+    if args.get(1).map(|s| s.as_str()) == Some("svg") {
+        svg(args, config);
+        return;
+    }
     let ip = if args.len() >= 2 {
         args[1].clone()
     } else {
@@ -754,7 +1026,7 @@ fn main() {
     {
         let ip = ip.clone();
         std::thread::spawn(move || {
-            websocket.listen(&format!("{}:{}", ip, wsport)).unwrap();
+            websocket.listen(format!("{}:{}", ip, wsport)).unwrap();
         });
     }
 
@@ -762,8 +1034,21 @@ fn main() {
         ip: format!("{}:{}", ip, socketport),
         streams: Vec::new(),
         players: Vec::new(),
-        game: Quoridor::new(),
-        broadcaster: broadcaster,
+        game,
+        broadcaster,
+        draw_offers: Vec::new(),
+        undo_offers: Vec::new(),
+        record_format,
+        turn_policy,
+        invalid_policy,
+        time_limit,
+        invalid_count: 0,
+        turn_penalty: Duration::from_secs(0),
+        penalties: Vec::new(),
+        start_time: SystemTime::now(),
+        turn_start: Instant::now(),
+        think_times: Vec::new(),
+        used: Vec::new(),
     };
     match server.start() {
         Ok(_) => (),
@@ -0,0 +1,149 @@
+//! Algebraic notation: columns `a`, `b`, ... from the left and rows `1`, `2`, ... from
+//! the bottom, so white starts on `e1` of a 9x9 board. A wall is named by the square
+//! south-west of its centre followed by `h` or `v`, as in `d7h`.
+use config::GameConfig;
+use quoridor::{Command, Quoridor};
+use wall::Dir;
+
+impl Command {
+    /// This command in algebraic notation.
+    pub fn to_notation(&self, config: &GameConfig) -> String {
+        let column = |x: usize| (b'a' + x as u8) as char;
+        match *self {
+            Command::Move(y, x) => format!("{}{}", column(x), config.height - y),
+            Command::Put(y, x, dir) => {
+                let suffix = match dir {
+                    Dir::Horizontal => 'h',
+                    Dir::Vertical => 'v',
+                };
+                format!("{}{}{}", column(x), config.height - 1 - y, suffix)
+            }
+        }
+    }
+
+    /// Parses one action in algebraic notation, such as `e2` or `d7h`.
+    ///
+    /// Returns `None` unless the square or wall lies on the board of `config`.
+    pub fn parse_notation(config: &GameConfig, input: &str) -> Option<Self> {
+        let input = input.trim();
+        let column = input.chars().next()?;
+        if !column.is_ascii_lowercase() {
+            return None;
+        }
+        let x = (column as u8 - b'a') as usize;
+        let rest = &input[1..];
+        let (digits, dir) = match rest.chars().last()? {
+            'h' => (&rest[..rest.len() - 1], Some(Dir::Horizontal)),
+            'v' => (&rest[..rest.len() - 1], Some(Dir::Vertical)),
+            _ => (rest, None),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let row = digits.parse::<usize>().ok()?;
+        let (h, w) = (config.height, config.width);
+        match dir {
+            None if 1 <= row && row <= h && x < w => Some(Command::Move(h - row, x)),
+            Some(dir) if 1 <= row && row < h && x < w - 1 => {
+                Some(Command::Put(h - 1 - row, x, dir))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Quoridor {
+    /// The actions played so far in algebraic notation, separated by spaces.
+    pub fn notation(&self) -> String {
+        self.record()
+            .iter()
+            .map(|r| r.command().to_notation(self.config()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Plays a game written in algebraic notation from the initial position of `config`.
+    ///
+    /// Move numbers such as `1.` are skipped.
+    pub fn from_notation(config: GameConfig, text: &str) -> Result<Self, String> {
+        config.validate()?;
+        let mut q = Quoridor::new(config);
+        for token in text.split_whitespace() {
+            let number = token.strip_suffix('.').unwrap_or("");
+            if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) {
+                continue;
+            }
+            let com = Command::parse_notation(&config, token)
+                .ok_or_else(|| format!("Invalid move {}", token))?;
+            q.play(&com).map_err(|e| format!("{}: {}", token, e))?;
+        }
+        Ok(q)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::GameConfig;
+    use quoridor::{Command, Quoridor};
+    use testutil::Rng;
+    use wall::Dir;
+
+    #[test]
+    fn round_trip() {
+        let mut rng = Rng::new(16);
+        for &size in &[5, 9, 11] {
+            let config = GameConfig::new(size, 10, 2).unwrap();
+            let mut q = Quoridor::new(config);
+            for _ in 0..20 {
+                if q.winner().is_some() {
+                    break;
+                }
+                for com in q.legal_actions() {
+                    let text = com.to_notation(&config);
+                    assert_eq!(
+                        Command::parse_notation(&config, &text),
+                        Some(com),
+                        "{}",
+                        text
+                    );
+                }
+                let com = rng.action(&q);
+                q.play(&com).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn anchors() {
+        let config = GameConfig::default();
+        let q = Quoridor::new(config);
+        assert_eq!(
+            Command::Move(q.pawns()[0].0, q.pawns()[0].1).to_notation(&config),
+            "e1"
+        );
+        assert_eq!(
+            Command::Move(q.pawns()[1].0, q.pawns()[1].1).to_notation(&config),
+            "e9"
+        );
+        assert_eq!(
+            Command::parse_notation(&config, "d7h"),
+            Some(Command::Put(1, 3, Dir::Horizontal))
+        );
+        assert_eq!(
+            Command::parse_notation(&config, "a1v"),
+            Some(Command::Put(7, 0, Dir::Vertical))
+        );
+        for text in &["i8h", "a9h", "a0", "z1", "j1", "", "e", "eh", "e1x", "E1"] {
+            assert_eq!(Command::parse_notation(&config, text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn move_numbers() {
+        let config = GameConfig::default();
+        let q = Quoridor::from_notation(config, "1. e2 e8 2. d7h").unwrap();
+        assert_eq!(q.notation(), "e2 e8 d7h");
+        assert!(Quoridor::from_notation(config, "1. e2 x9").is_err());
+        assert!(Quoridor::from_notation(config, "e3").is_err());
+    }
+}
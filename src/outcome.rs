@@ -0,0 +1,68 @@
+use std::fmt;
+use wall::Colour;
+
+/// Why a game was drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawReason {
+    Repetition,
+    MaxPlies,
+    Agreement,
+}
+
+impl fmt::Display for DrawReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            DrawReason::Repetition => "repetition",
+            DrawReason::MaxPlies => "move limit",
+            DrawReason::Agreement => "agreement",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Why a player lost without reaching the end of the game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForfeitReason {
+    /// Sent a command while another player was to move.
+    OutOfTurn,
+    /// Sent too many commands that could not be read or played.
+    InvalidMoves,
+    /// Used up its time for the game.
+    Timeout,
+    /// Closed the connection before the game ended.
+    Disconnected,
+}
+
+impl fmt::Display for ForfeitReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            ForfeitReason::OutOfTurn => "moving out of turn",
+            ForfeitReason::InvalidMoves => "invalid moves",
+            ForfeitReason::Timeout => "running out of time",
+            ForfeitReason::Disconnected => "disconnecting",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Result of a finished game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The player in this seat reached its goal edge.
+    Win(usize),
+    Draw(DrawReason),
+    /// The player in this seat lost by breaking the server rules.
+    Forfeit(usize, ForfeitReason),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Win(id) => write!(f, "{} wins", Colour::from_index(*id)),
+            Outcome::Draw(reason) => write!(f, "draw by {}", reason),
+            Outcome::Forfeit(id, reason) => {
+                write!(f, "{} forfeits by {}", Colour::from_index(*id), reason)
+            }
+        }
+    }
+}
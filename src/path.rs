@@ -0,0 +1,246 @@
+use quoridor::Quoridor;
+use std::collections::VecDeque;
+
+/// How other pawns are treated when measuring a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathMode {
+    /// Only walls matter, as in the path-blocking rule.
+    IgnorePawns,
+    /// The other pawns stay where they are and can be jumped over or block a square.
+    ConsiderPawns,
+}
+
+impl Quoridor {
+    /// Number of moves player `id` needs to reach its goal edge, or `None` if it cannot.
+    pub fn distance(&self, id: usize, mode: PathMode) -> Option<usize> {
+        let (y, x) = self.pawn(id);
+        match mode {
+            PathMode::IgnorePawns => self.table().distance(y, x, self.config().goal(id)),
+            PathMode::ConsiderPawns => self.search(id, (y, x)).map(|path| path.len() - 1),
+        }
+    }
+
+    /// One shortest route of player `id` to its goal edge, starting with its own square.
+    pub fn shortest_path(&self, id: usize, mode: PathMode) -> Option<Vec<(usize, usize)>> {
+        let (y, x) = self.pawn(id);
+        match mode {
+            PathMode::IgnorePawns => self.table().shortest_path(y, x, self.config().goal(id)),
+            PathMode::ConsiderPawns => self.search(id, (y, x)),
+        }
+    }
+
+    /// Number of moves player `id` would need from every square, indexed `[y][x]`.
+    ///
+    /// With [`PathMode::ConsiderPawns`] this runs one search per square.
+    pub fn distance_map(&self, id: usize, mode: PathMode) -> Vec<Vec<Option<usize>>> {
+        let goal = self.config().goal(id);
+        match mode {
+            PathMode::IgnorePawns => self.table().distance_map(goal),
+            PathMode::ConsiderPawns => {
+                let (h, w) = (self.config().height, self.config().width);
+                let mut map = vec![vec![None; w]; h];
+                for (y, row) in map.iter_mut().enumerate() {
+                    for (x, cell) in row.iter_mut().enumerate() {
+                        let taken = self
+                            .pawns()
+                            .iter()
+                            .enumerate()
+                            .any(|(i, &p)| i != id && p == (y, x));
+                        if !taken {
+                            *cell = self.search(id, (y, x)).map(|path| path.len() - 1);
+                        }
+                    }
+                }
+                map
+            }
+        }
+    }
+
+    // 他の駒を固定して from からゴールまで幅優先探索する
+    fn search(&self, id: usize, from: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let config = self.config();
+        let goal = config.goal(id);
+        let index = |(y, x): (usize, usize)| y * config.width + x;
+        let mut prev: Vec<Option<(usize, usize)>> = vec![None; config.height * config.width];
+        let mut visited = vec![false; config.height * config.width];
+        let mut queue = VecDeque::new();
+        visited[index(from)] = true;
+        queue.push_back(from);
+        while let Some(pos) = queue.pop_front() {
+            if config.on_side(pos, goal) {
+                let mut path = vec![pos];
+                let mut cur = pos;
+                while let Some(p) = prev[index(cur)] {
+                    path.push(p);
+                    cur = p;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.moves_from(id, pos) {
+                if !visited[index(next)] {
+                    visited[index(next)] = true;
+                    prev[index(next)] = Some(pos);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::GameConfig;
+    use testutil::Rng;
+    use DPOS;
+
+    // 白と黒の駒の位置と壁の行から socket format の局面を作る
+    fn position(pawns: &str, walls: &[(usize, usize, u8)]) -> Quoridor {
+        let mut grid = [[0; 8]; 8];
+        for &(y, x, w) in walls {
+            grid[y][x] = w;
+        }
+        let mut text = format!("{}\n", pawns);
+        for row in grid.iter() {
+            let row: Vec<String> = row.iter().map(|w| w.to_string()).collect();
+            text += &format!("{}\n", row.join(" "));
+        }
+        Quoridor::from_socketformat(GameConfig::default(), &text, 0).unwrap()
+    }
+
+    #[test]
+    fn open_board() {
+        let q = Quoridor::default();
+        for &mode in &[PathMode::IgnorePawns, PathMode::ConsiderPawns] {
+            assert_eq!(q.distance(0, mode), Some(8));
+            assert_eq!(q.distance(1, mode), Some(8));
+            let path = q.shortest_path(0, mode).unwrap();
+            assert_eq!(path.len(), 9);
+            assert_eq!(path[0], (8, 4));
+        }
+        let map = q.distance_map(0, PathMode::IgnorePawns);
+        for (y, row) in map.iter().enumerate() {
+            assert!(row.iter().all(|&d| d == Some(y)));
+        }
+        // 黒のいるマスには行けないが，隣から斜めに跳べるので他は変わらない
+        let map = q.distance_map(0, PathMode::ConsiderPawns);
+        for (y, row) in map.iter().enumerate() {
+            for (x, &d) in row.iter().enumerate() {
+                assert_eq!(d, if (y, x) == (0, 4) { None } else { Some(y) });
+            }
+        }
+    }
+
+    #[test]
+    fn jump_shortens_path() {
+        let q = position("4 2 4 1 10 10", &[]);
+        assert_eq!(q.distance(0, PathMode::IgnorePawns), Some(2));
+        assert_eq!(q.distance(0, PathMode::ConsiderPawns), Some(1));
+        assert_eq!(
+            q.shortest_path(0, PathMode::ConsiderPawns),
+            Some(vec![(2, 4), (0, 4)])
+        );
+    }
+
+    #[test]
+    fn wall_makes_detour() {
+        // 白の真上をふさぐ横壁
+        let q = position("4 8 4 0 9 10", &[(7, 4, 1)]);
+        for &mode in &[PathMode::IgnorePawns, PathMode::ConsiderPawns] {
+            assert_eq!(q.distance(0, mode), Some(9));
+            let path = q.shortest_path(0, mode).unwrap();
+            assert_eq!(path[1], (8, 3));
+        }
+    }
+
+    // 探索を使わずに数える幅優先探索
+    fn reference(q: &Quoridor, id: usize, mode: PathMode) -> Option<usize> {
+        let config = q.config();
+        let mut dist = vec![vec![None; config.width]; config.height];
+        let start = q.pawn(id);
+        dist[start.0][start.1] = Some(0);
+        let mut frontier = vec![start];
+        let mut d = 0;
+        while !frontier.is_empty() {
+            if frontier
+                .iter()
+                .any(|&pos| config.on_side(pos, config.goal(id)))
+            {
+                return Some(d);
+            }
+            d += 1;
+            let mut next = Vec::new();
+            for &(y, x) in &frontier {
+                let steps = match mode {
+                    PathMode::IgnorePawns => DPOS
+                        .iter()
+                        .filter(|&&(dy, dx)| q.table().can_step(y as i8, x as i8, dy, dx))
+                        .map(|&(dy, dx)| ((y as i8 + dy) as usize, (x as i8 + dx) as usize))
+                        .collect(),
+                    PathMode::ConsiderPawns => q.moves_from(id, (y, x)),
+                };
+                for (ny, nx) in steps {
+                    if dist[ny][nx].is_none() {
+                        dist[ny][nx] = Some(d);
+                        next.push((ny, nx));
+                    }
+                }
+            }
+            frontier = next;
+        }
+        None
+    }
+
+    #[test]
+    fn random_positions() {
+        let mut rng = Rng::new(5);
+        for &(size, players) in &[(5, 2), (7, 4), (9, 2)] {
+            let config = GameConfig::new(size, 2 * size, players).unwrap();
+            for _ in 0..10 {
+                let mut q = Quoridor::new(config);
+                while q.winner().is_none() && q.record().len() < 60 {
+                    for id in 0..players {
+                        for &mode in &[PathMode::IgnorePawns, PathMode::ConsiderPawns] {
+                            let d = q.distance(id, mode);
+                            assert_eq!(d, reference(&q, id, mode));
+                            let (y, x) = q.pawn(id);
+                            // 全マスを調べるので数手おきに
+                            if q.record().len().is_multiple_of(8) {
+                                assert_eq!(q.distance_map(id, mode)[y][x], d);
+                            }
+                            let path = match q.shortest_path(id, mode) {
+                                Some(path) => path,
+                                None => continue,
+                            };
+                            assert_eq!(Some(path.len() - 1), d);
+                            assert_eq!(path[0], (y, x));
+                            assert!(config.on_side(path[path.len() - 1], config.goal(id)));
+                            for step in path.windows(2) {
+                                let (from, to) = (step[0], step[1]);
+                                let legal = match mode {
+                                    PathMode::IgnorePawns => {
+                                        let dy = to.0 as i8 - from.0 as i8;
+                                        let dx = to.1 as i8 - from.1 as i8;
+                                        dy.abs() + dx.abs() == 1
+                                            && q.table().can_step(
+                                                from.0 as i8,
+                                                from.1 as i8,
+                                                dy,
+                                                dx,
+                                            )
+                                    }
+                                    PathMode::ConsiderPawns => q.moves_from(id, from).contains(&to),
+                                };
+                                assert!(legal, "{:?} -> {:?}", from, to);
+                            }
+                        }
+                    }
+                    let com = rng.action(&q);
+                    q.play(&com).unwrap();
+                }
+            }
+        }
+    }
+}
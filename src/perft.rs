@@ -0,0 +1,105 @@
+use quoridor::{Command, Quoridor};
+
+impl Quoridor {
+    /// Number of positions reached by playing every legal action sequence of `depth` plies.
+    ///
+    /// A won position has no actions, so it only counts when it is a leaf. Draw rules are
+    /// ignored.
+    pub fn perft(&mut self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if self.winner().is_some() {
+            return 0;
+        }
+        let actions = self.legal_actions();
+        if depth == 1 {
+            return actions.len() as u64;
+        }
+        let mut nodes = 0;
+        for com in actions {
+            let undo = self.apply(com);
+            nodes += self.perft(depth - 1);
+            self.unapply(undo);
+        }
+        nodes
+    }
+
+    /// [`Quoridor::perft`] split by the first action, in [`Quoridor::legal_actions`] order.
+    pub fn divide(&mut self, depth: usize) -> Vec<(Command, u64)> {
+        if depth == 0 || self.winner().is_some() {
+            return Vec::new();
+        }
+        let mut result = Vec::new();
+        for com in self.legal_actions() {
+            let undo = self.apply(com);
+            result.push((com, self.perft(depth - 1)));
+            self.unapply(undo);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::GameConfig;
+    use quoridor::{Command, Quoridor};
+    use wall::Dir;
+
+    // movable と settable を全マスで試して数える
+    fn brute(q: &mut Quoridor, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if q.winner().is_some() {
+            return 0;
+        }
+        let config = *q.config();
+        let mut actions = Vec::new();
+        for y in 0..config.height {
+            for x in 0..config.width {
+                if q.movable(y, x).is_ok() {
+                    actions.push(Command::Move(y, x));
+                }
+                for &dir in &[Dir::Horizontal, Dir::Vertical] {
+                    if q.settable(y, x, dir).is_ok() {
+                        actions.push(Command::Put(y, x, dir));
+                    }
+                }
+            }
+        }
+        let mut nodes = 0;
+        for com in actions {
+            q.play(&com).unwrap();
+            nodes += brute(q, depth - 1);
+            q.undo();
+        }
+        nodes
+    }
+
+    #[test]
+    fn initial_position() {
+        let mut q = Quoridor::default();
+        assert_eq!(q.perft(0), 1);
+        assert_eq!(q.perft(1), 131);
+        assert_eq!(q.perft(2), 16677);
+        let divide = q.divide(2);
+        assert_eq!(divide.len(), 131);
+        assert_eq!(divide.iter().map(|&(_, n)| n).sum::<u64>(), 16677);
+    }
+
+    #[test]
+    fn small_boards_match_brute_force() {
+        for &(size, walls, players) in &[(3, 1, 2), (5, 1, 2), (5, 1, 4)] {
+            let mut q = Quoridor::new(GameConfig::new(size, walls, players).unwrap());
+            assert_eq!(
+                q.perft(3),
+                brute(&mut q, 3),
+                "{}x{} {}",
+                size,
+                size,
+                players
+            );
+        }
+    }
+}
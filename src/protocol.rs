@@ -0,0 +1,54 @@
+//! Errors the judge sends back to players.
+use quoridor_judge::IllegalAction;
+use std::fmt;
+
+/// Why the judge refused a line from a player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProtocolError {
+    /// The command breaks the rules.
+    Illegal(IllegalAction),
+    /// The line is neither a command nor a chat message.
+    Unparsable,
+    /// The line is longer than the server accepts.
+    LineTooLong,
+    /// The line is not valid UTF-8.
+    InvalidUtf8,
+    /// A greeting other than `HELLO <name> [version]`.
+    BadHello,
+    /// A greeting sent after the game started.
+    LateHello,
+}
+
+impl ProtocolError {
+    /// Number sent in the `error` line; rule violations keep the codes of
+    /// `IllegalAction::code`.
+    pub fn code(&self) -> u16 {
+        match self {
+            ProtocolError::Illegal(e) => e.code(),
+            ProtocolError::Unparsable => 303,
+            ProtocolError::LineTooLong => 304,
+            ProtocolError::InvalidUtf8 => 305,
+            ProtocolError::BadHello => 306,
+            ProtocolError::LateHello => 307,
+        }
+    }
+}
+
+impl From<IllegalAction> for ProtocolError {
+    fn from(e: IllegalAction) -> Self {
+        ProtocolError::Illegal(e)
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Illegal(e) => e.fmt(f),
+            ProtocolError::Unparsable => write!(f, "Cannot read the command"),
+            ProtocolError::LineTooLong => write!(f, "The line is too long"),
+            ProtocolError::InvalidUtf8 => write!(f, "The line is not valid UTF-8"),
+            ProtocolError::BadHello => write!(f, "Expected HELLO <name> [version]"),
+            ProtocolError::LateHello => write!(f, "The game has already started"),
+        }
+    }
+}
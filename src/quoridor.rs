@@ -0,0 +1,1220 @@
+use base64::{Alphabet, BitReader, BitWriter};
+use config::{GameConfig, MAX_PLAYERS};
+use error::IllegalAction;
+use outcome::{DrawReason, ForfeitReason, Outcome};
+use std::fmt;
+use wall::{Colour, Dir, WallTable};
+use zobrist;
+use {DPOS, DYDX2MOVEDIR};
+
+fn pos_to_u8(config: &GameConfig, (y, x): (usize, usize)) -> u8 {
+    ((config.height - 1 - y) * config.width + x) as u8
+}
+fn wall_to_u8(config: &GameConfig, (y, x): (usize, usize)) -> u8 {
+    ((config.height - 2 - y) * (config.width - 1) + x) as u8
+}
+fn u8_to_pos(config: &GameConfig, n: u8) -> Result<(usize, usize), String> {
+    let n = n as usize;
+    if n >= config.height * config.width {
+        return Err(format!("Square {} is out of bounds", n));
+    }
+    Ok((config.height - 1 - n / config.width, n % config.width))
+}
+fn u8_to_wall(config: &GameConfig, n: u8) -> Result<(usize, usize), String> {
+    let (n, w) = (n as usize, config.width - 1);
+    if n >= (config.height - 1) * w {
+        return Err(format!("Wall {} is out of bounds", n));
+    }
+    Ok((config.height - 2 - n / w, n % w))
+}
+
+/// An action sent by a player.
+///
+/// Coordinates are `(y, x)`; walls are addressed by their centre.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Move(usize, usize),
+    Put(usize, usize, Dir),
+}
+
+impl Command {
+    /// Parses the protocol syntax `x y` or `x y H|V`.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input_vec: Vec<&str> = input.split_whitespace().collect();
+        if input_vec.len() < 2 {
+            return None;
+        }
+        let x = input_vec[0].parse::<usize>().ok()?;
+        let y = input_vec[1].parse::<usize>().ok()?;
+        if input_vec.len() < 3 {
+            Some(Command::Move(y, x))
+        } else {
+            let dir = Dir::parse(input_vec[2])?;
+            Some(Command::Put(y, x, dir))
+        }
+    }
+}
+
+/// Formats in the syntax [`Command::parse`] reads.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Move(y, x) => write!(f, "{} {}", x, y),
+            Command::Put(y, x, dir) => write!(f, "{} {} {}", x, y, dir),
+        }
+    }
+}
+
+/// One ply of the game history.
+///
+/// `Piece` holds the squares the pawn moved from and to, so jumps are kept exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Record {
+    Piece((usize, usize), (usize, usize)),
+    Wall(usize, usize, Dir),
+}
+
+impl Record {
+    /// Move direction of a pawn move as used by the history format, `None` for walls.
+    ///
+    /// A straight jump gets the code of a one-square step in the same direction: when
+    /// the history is replayed the adjacent square is occupied, so the move can only
+    /// be the jump.
+    pub fn movedir(&self) -> Option<u8> {
+        match *self {
+            Record::Piece(from, to) => {
+                let dy = (from.0 as i8 - to.0 as i8).signum();
+                let dx = (from.1 as i8 - to.1 as i8).signum();
+                Some(DYDX2MOVEDIR[(dy + 1) as usize][(dx + 1) as usize])
+            }
+            Record::Wall(..) => None,
+        }
+    }
+
+    /// The command that plays this ply.
+    pub fn command(&self) -> Command {
+        match *self {
+            Record::Piece(_, (y, x)) => Command::Move(y, x),
+            Record::Wall(y, x, dir) => Command::Put(y, x, dir),
+        }
+    }
+
+    /// Converts a move direction back to a unit step `(dy, dx)`.
+    pub fn to_dydx(d: u8) -> Option<(i8, i8)> {
+        let dy = match d {
+            0 | 1 | 7 => -1,
+            2 | 6 => 0,
+            3..=5 => 1,
+
+            _ => {
+                return None;
+            }
+        };
+        let dx = match d {
+            1..=3 => 1,
+            0 | 4 => 0,
+            5..=7 => -1,
+            _ => {
+                return None;
+            }
+        };
+        Some((dy, dx))
+    }
+}
+
+/// Everything [`Quoridor::unapply`] needs to take back one action.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Undo {
+    command: Command,
+    from: (usize, usize),
+    last_move: Option<(usize, usize)>,
+}
+
+/// A Quoridor game for 2 or 4 players: position, side to move and history.
+///
+/// Players are identified by their seat index, which is also the index of their [`Colour`].
+#[derive(Clone)]
+pub struct Quoridor {
+    config: GameConfig,
+    table: WallTable,
+    pawns: [(usize, usize); MAX_PLAYERS],
+    turn: usize,
+    last_move: Option<(usize, usize)>,
+    turn_num: u16,
+    wall_nums: [usize; MAX_PLAYERS],
+    record: Vec<Record>,
+    hash: u64,
+    // 初期局面と各手の後の局面のハッシュ
+    hashes: Vec<u64>,
+    agreed_draw: bool,
+    forfeited: Option<(usize, ForfeitReason)>,
+    // 初期配置以外の局面から始めたか
+    custom_start: bool,
+}
+
+impl Quoridor {
+    /// Creates the initial position of `config` with player 0 to move.
+    pub fn new(config: GameConfig) -> Self {
+        let mut pawns = [(0, 0); MAX_PLAYERS];
+        for (id, pawn) in pawns.iter_mut().enumerate().take(config.player_num) {
+            *pawn = config.start(id);
+        }
+        let mut q = Quoridor {
+            config,
+            table: WallTable::new(config.height, config.width),
+            pawns,
+            turn: 0,
+            last_move: None,
+            turn_num: 1,
+            wall_nums: [config.wall_limit; MAX_PLAYERS],
+            record: Vec::new(),
+            hash: 0,
+            hashes: Vec::new(),
+            agreed_draw: false,
+            forfeited: None,
+            custom_start: false,
+        };
+        q.hash = q.compute_hash();
+        q.hashes.push(q.hash);
+        q
+    }
+
+    /// Zobrist hash of the position: pawns, walls, walls left and side to move.
+    ///
+    /// Kept up to date by every move, so equal positions reached by different orders of
+    /// play share a hash.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Computes [`Quoridor::hash`] from scratch.
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = zobrist::turn(self.turn);
+        for (id, &(y, x)) in self.pawns().iter().enumerate() {
+            hash ^= zobrist::pawn(id, y * self.config.width + x);
+            hash ^= zobrist::wall_num(id, self.wall_nums[id]);
+        }
+        for ((y, x), dir, _) in self.table.walls() {
+            hash ^= zobrist::wall(y * (self.config.width - 1) + x, dir);
+        }
+        hash
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    pub fn table(&self) -> &WallTable {
+        &self.table
+    }
+    /// Pawn squares in seat order.
+    pub fn pawns(&self) -> &[(usize, usize)] {
+        &self.pawns[..self.config.player_num]
+    }
+    pub fn pawn(&self, id: usize) -> (usize, usize) {
+        self.pawns[id]
+    }
+    /// Seat of the player to move.
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+    /// Centre of the wall placed on the previous ply, if any.
+    pub fn last_move(&self) -> Option<(usize, usize)> {
+        self.last_move
+    }
+    pub fn turn_num(&self) -> u16 {
+        self.turn_num
+    }
+    /// Walls player `id` has left.
+    pub fn wall_num(&self, id: usize) -> usize {
+        self.wall_nums[id]
+    }
+
+    pub fn record(&self) -> &[Record] {
+        &self.record
+    }
+
+    fn prev_turn(&self) -> usize {
+        (self.turn + self.config.player_num - 1) % self.config.player_num
+    }
+
+    /// Takes back the last ply. Returns `false` if there is none.
+    pub fn undo(&mut self) -> bool {
+        let r = match self.record.pop() {
+            Some(r) => r,
+            None => return false,
+        };
+        self.hashes.pop();
+        self.agreed_draw = false;
+        self.forfeited = None;
+        let last_move = match self.record.last() {
+            Some(Record::Wall(y, x, _)) => Some((*y, *x)),
+            Some(Record::Piece(..)) | None => None,
+        };
+        let undo = match r {
+            Record::Piece(from, (y, x)) => Undo {
+                command: Command::Move(y, x),
+                from,
+                last_move,
+            },
+            Record::Wall(y, x, dir) => Undo {
+                command: Command::Put(y, x, dir),
+                from: self.pawns[self.prev_turn()],
+                last_move,
+            },
+        };
+        self.unapply(undo);
+        true
+    }
+
+    /// Returns the seat of the winner once a pawn reaches its goal edge.
+    pub fn winner(&self) -> Option<usize> {
+        (0..self.config.player_num)
+            .find(|&id| self.config.on_side(self.pawns[id], self.config.goal(id)))
+    }
+
+    /// Returns the result once the game has been won or drawn under the configured
+    /// [`DrawRules`](::DrawRules).
+    pub fn is_over(&self) -> Option<Outcome> {
+        if let Some((id, reason)) = self.forfeited {
+            return Some(Outcome::Forfeit(id, reason));
+        }
+        if let Some(id) = self.winner() {
+            return Some(Outcome::Win(id));
+        }
+        let rules = &self.config.draw;
+        if self.agreed_draw {
+            Some(Outcome::Draw(DrawReason::Agreement))
+        } else if rules.repetition.is_some_and(|n| n <= self.repetitions()) {
+            Some(Outcome::Draw(DrawReason::Repetition))
+        } else if rules.max_plies.is_some_and(|n| n <= self.record.len()) {
+            Some(Outcome::Draw(DrawReason::MaxPlies))
+        } else {
+            None
+        }
+    }
+
+    /// How many times the current position has occurred in the game so far.
+    pub fn repetitions(&self) -> usize {
+        self.hashes.iter().filter(|&&h| h == self.hash).count()
+    }
+
+    /// Ends the game as a draw agreed by every player.
+    pub fn agree_draw(&mut self) -> Result<(), IllegalAction> {
+        if !self.config.draw.agreement {
+            return Err(IllegalAction::DrawNotAllowed);
+        }
+        self.agreed_draw = true;
+        Ok(())
+    }
+
+    /// Ends the game with player `id` losing for `reason`.
+    pub fn forfeit(&mut self, id: usize, reason: ForfeitReason) {
+        self.forfeited = Some((id, reason));
+    }
+
+    /// Checks whether the side to move may put a wall centred at `(y, x)`, the same
+    /// coordinates as [`Command::Put`] and [`Quoridor::legal_walls`].
+    pub fn settable(&self, y: usize, x: usize, dir: Dir) -> Result<(), IllegalAction> {
+        if self.wall_nums[self.turn] == 0 {
+            return Err(IllegalAction::NoWallsLeft);
+        }
+        if y >= self.config.height - 1 || x >= self.config.width - 1 {
+            return Err(IllegalAction::WallOutOfBounds(y, x));
+        }
+        let (cy, cx) = (y, x);
+        let (y, x) = (cy as i8, cx as i8);
+        if let Some((d, _)) = self.table.get(y, x) {
+            return Err(if d == dir {
+                IllegalAction::WallOverlap(cy, cx)
+            } else {
+                IllegalAction::WallCrossing(cy, cx)
+            });
+        }
+        let overlap = match dir {
+            Dir::Horizontal => {
+                self.table.checkwalldir(y, x - 1, Dir::Horizontal)
+                    || self.table.checkwalldir(y, x + 1, Dir::Horizontal)
+            }
+            Dir::Vertical => {
+                self.table.checkwalldir(y - 1, x, Dir::Vertical)
+                    || self.table.checkwalldir(y + 1, x, Dir::Vertical)
+            }
+        };
+        if overlap {
+            return Err(IllegalAction::WallOverlap(cy, cx));
+        }
+
+        let mut new_table = self.table;
+        new_table.set(y, x, dir, Colour::from_index(self.turn));
+        let blocked =
+            self.pawns().iter().enumerate().position(|(id, &(py, px))| {
+                new_table.distance(py, px, self.config.goal(id)).is_none()
+            });
+        match blocked {
+            Some(player) => Err(IllegalAction::BlocksPath {
+                y: cy,
+                x: cx,
+                player,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Every wall the side to move may put, as `(y, x, dir)` wall centres.
+    pub fn legal_walls(&self) -> Vec<(usize, usize, Dir)> {
+        let mut walls = Vec::new();
+        if self.wall_nums[self.turn] == 0 {
+            return walls;
+        }
+        // 各プレイヤーの最短経路が縦・横に進むマス (上側・左側のマス)
+        let mut cuts = Vec::new();
+        for (id, &(py, px)) in self.pawns().iter().enumerate() {
+            let path = match self.table.shortest_path(py, px, self.config.goal(id)) {
+                Some(path) => path,
+                None => continue,
+            };
+            let (mut down, mut right) = (0, 0);
+            for step in path.windows(2) {
+                let ((y1, x1), (y2, x2)) = (step[0], step[1]);
+                if x1 == x2 {
+                    down |= self.table.square_bit(y1.min(y2), x1);
+                } else {
+                    right |= self.table.square_bit(y1, x1.min(x2));
+                }
+            }
+            cuts.push((id, down, right));
+        }
+
+        let c = Colour::from_index(self.turn);
+        for y in 0..self.config.height - 1 {
+            for x in 0..self.config.width - 1 {
+                for &dir in &[Dir::Horizontal, Dir::Vertical] {
+                    if !self.table.fits(y as i8, x as i8, dir) {
+                        continue;
+                    }
+                    let cut = self.table.cut_squares(y, x, dir);
+                    let mut new_table = self.table;
+                    new_table.set(y as i8, x as i8, dir, c);
+                    // 最短経路を切らない壁は経路を調べ直さなくてよい
+                    let legal = cuts.iter().all(|&(id, down, right)| {
+                        let touched = match dir {
+                            Dir::Horizontal => down & cut != 0,
+                            Dir::Vertical => right & cut != 0,
+                        };
+                        let (py, px) = self.pawns[id];
+                        !touched || new_table.distance(py, px, self.config.goal(id)).is_some()
+                    });
+                    if legal {
+                        walls.push((y, x, dir));
+                    }
+                }
+            }
+        }
+        walls
+    }
+
+    // 自分以外の駒があるか
+    fn occupied(&self, id: usize, y: i8, x: i8) -> bool {
+        self.pawns()
+            .iter()
+            .enumerate()
+            .any(|(i, &(py, px))| i != id && (py as i8, px as i8) == (y, x))
+    }
+
+    /// Squares the side to move can step or jump to.
+    ///
+    /// A pawn jumps straight over an adjacent pawn, or diagonally when the straight
+    /// jump is blocked by a wall, the board edge or another pawn.
+    pub fn next_moves(&self) -> Vec<(usize, usize)> {
+        self.moves_from(self.turn, self.pawns[self.turn])
+    }
+
+    /// Squares player `id` could move to if its pawn stood on `from`, the other pawns
+    /// staying where they are.
+    pub fn moves_from(&self, id: usize, from: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut moves = Vec::new();
+        let me = (from.0 as i8, from.1 as i8);
+        for &(dy, dx) in DPOS.iter() {
+            if !self.table.can_step(me.0, me.1, dy, dx) {
+                continue;
+            }
+            let (y, x) = (dy + me.0, dx + me.1);
+            if self.occupied(id, y, x) {
+                let (y2, x2) = (y + dy, x + dx);
+                if !self.config.in_area(y2 as usize, x2 as usize)
+                    || self.table.exist_wall(y, x, dy, dx)
+                    || self.occupied(id, y2, x2)
+                {
+                    for (dy, dx) in DPOS.iter() {
+                        if self.table.exist_wall(y, x, *dy, *dx) {
+                            continue;
+                        }
+                        let (y2, x2) = (y + dy, x + dx);
+                        if me == (y2, x2)
+                            || !self.config.in_area(y2 as usize, x2 as usize)
+                            || self.occupied(id, y2, x2)
+                        {
+                            continue;
+                        }
+                        let m = (y2 as usize, x2 as usize);
+                        if !moves.contains(&m) {
+                            moves.push(m);
+                        }
+                    }
+                } else {
+                    moves.push((y2 as usize, x2 as usize));
+                }
+            } else {
+                moves.push((y as usize, x as usize));
+            }
+        }
+
+        moves
+    }
+
+    /// Checks whether the side to move may move its pawn to `(y, x)`.
+    pub fn movable(&self, y: usize, x: usize) -> Result<(), IllegalAction> {
+        if !self.config.in_area(y, x) {
+            return Err(IllegalAction::MoveOutOfBounds(y, x));
+        }
+        if self.next_moves().contains(&(y, x)) {
+            return Ok(());
+        }
+        let (py, px) = self.pawns[self.turn];
+        if self.pawns().contains(&(y, x)) {
+            return Err(IllegalAction::SquareOccupied(y, x));
+        }
+        let (ty, tx) = (y as i8, x as i8);
+        let (py, px) = (py as i8, px as i8);
+        if (ty - py).abs() + (tx - px).abs() == 1 {
+            return Err(IllegalAction::WallInTheWay(y, x));
+        }
+        // 隣の駒を飛び越えた先のマスか
+        let jump = DPOS.iter().any(|&(dy, dx)| {
+            let (oy, ox) = (py + dy, px + dx);
+            self.occupied(self.turn, oy, ox) && (ty - oy).abs() + (tx - ox).abs() == 1
+        });
+        if jump {
+            Err(IllegalAction::JumpBlocked(y, x))
+        } else {
+            Err(IllegalAction::NotAdjacent(y, x))
+        }
+    }
+
+    /// ASCII drawing of the board for logs.
+    pub fn display(&self) -> String {
+        let (h, w) = (self.config.height, self.config.width);
+        let mut table: Vec<Vec<char>> = vec![vec![' '; 2 * w - 1]; 2 * h - 1];
+
+        for ((i, j), dir, _) in self.table.walls() {
+            match dir {
+                Dir::Vertical => {
+                    table[2 * i][2 * j + 1] = '|';
+                    table[2 * (i + 1)][2 * j + 1] = '|';
+                }
+                Dir::Horizontal => {
+                    table[2 * i + 1][2 * j] = '-';
+                    table[2 * i + 1][2 * (j + 1)] = '-';
+                }
+            }
+        }
+        for i in 0..h - 1 {
+            for j in 0..w - 1 {
+                table[2 * i + 1][2 * j + 1] = '*';
+            }
+        }
+        for (id, &(y, x)) in self.pawns().iter().enumerate() {
+            table[2 * y][2 * x] = ['W', 'B', 'R', 'G'][id];
+        }
+        let mut s = String::new();
+        s += &(0..(2 * w + 1)).map(|_| "#").collect::<String>();
+        s += "\n";
+        for row in &table {
+            let row: String = row.iter().collect();
+            s += &format!("#{}#\n", row);
+        }
+        s += &(0..(2 * w + 1)).map(|_| "#").collect::<String>();
+        s += "\n";
+        s
+    }
+
+    /// Validates and plays `com` for the side to move.
+    pub fn play(&mut self, com: &Command) -> Result<(), IllegalAction> {
+        match com {
+            Command::Put(y, x, dir) => {
+                self.settable(*y, *x, *dir)?;
+                self.record.push(Record::Wall(*y, *x, *dir));
+            }
+            Command::Move(y, x) => {
+                self.movable(*y, *x)?;
+                self.record
+                    .push(Record::Piece(self.pawns[self.turn], (*y, *x)));
+            }
+        }
+        self.apply(*com);
+        self.hashes.push(self.hash);
+        Ok(())
+    }
+
+    /// Like [`Quoridor::play`], but first checks that player `id` is the side to move.
+    pub fn play_as(&mut self, id: usize, com: &Command) -> Result<(), IllegalAction> {
+        if id != self.turn {
+            return Err(IllegalAction::NotYourTurn(self.turn));
+        }
+        self.play(com)
+    }
+
+    /// Every legal action of the side to move: pawn moves first, then walls.
+    pub fn legal_actions(&self) -> Vec<Command> {
+        let mut actions: Vec<Command> = self
+            .next_moves()
+            .into_iter()
+            .map(|(y, x)| Command::Move(y, x))
+            .collect();
+        actions.extend(
+            self.legal_walls()
+                .into_iter()
+                .map(|(y, x, dir)| Command::Put(y, x, dir)),
+        );
+        actions
+    }
+
+    /// Plays `com` for the side to move without checking it or adding it to the record.
+    ///
+    /// Meant for search: `com` must be legal, and the returned [`Undo`] takes it back
+    /// with [`Quoridor::unapply`].
+    pub fn apply(&mut self, com: Command) -> Undo {
+        let undo = Undo {
+            command: com,
+            from: self.pawns[self.turn],
+            last_move: self.last_move,
+        };
+        let (id, w) = (self.turn, self.config.width);
+        match com {
+            Command::Put(y, x, dir) => {
+                self.table
+                    .set(y as i8, x as i8, dir, Colour::from_index(id));
+                self.hash ^= zobrist::wall(y * (w - 1) + x, dir)
+                    ^ zobrist::wall_num(id, self.wall_nums[id])
+                    ^ zobrist::wall_num(id, self.wall_nums[id] - 1);
+                self.wall_nums[id] -= 1;
+                self.last_move = Some((y, x));
+            }
+            Command::Move(y, x) => {
+                let (py, px) = self.pawns[id];
+                self.hash ^= zobrist::pawn(id, py * w + px) ^ zobrist::pawn(id, y * w + x);
+                self.pawns[id] = (y, x);
+                self.last_move = None;
+            }
+        }
+        self.turn = (id + 1) % self.config.player_num;
+        self.hash ^= zobrist::turn(id) ^ zobrist::turn(self.turn);
+        self.turn_num += 1;
+        undo
+    }
+
+    /// Takes back an action played with [`Quoridor::apply`].
+    pub fn unapply(&mut self, undo: Undo) {
+        let (id, w) = (self.prev_turn(), self.config.width);
+        self.hash ^= zobrist::turn(self.turn) ^ zobrist::turn(id);
+        self.turn = id;
+        self.turn_num -= 1;
+        match undo.command {
+            Command::Put(y, x, dir) => {
+                self.table.erase(y as i8, x as i8);
+                self.hash ^= zobrist::wall(y * (w - 1) + x, dir)
+                    ^ zobrist::wall_num(id, self.wall_nums[id])
+                    ^ zobrist::wall_num(id, self.wall_nums[id] + 1);
+                self.wall_nums[id] += 1;
+            }
+            Command::Move(y, x) => {
+                let (py, px) = undo.from;
+                self.hash ^= zobrist::pawn(id, y * w + x) ^ zobrist::pawn(id, py * w + px);
+                self.pawns[id] = undo.from;
+            }
+        }
+        self.last_move = undo.last_move;
+    }
+
+    // README.md
+    /// Text position sent to the players over TCP.
+    ///
+    /// The first line holds `x y` of every pawn followed by every wall count, in seat order.
+    pub fn socketformat(&self) -> String {
+        let mut fields = Vec::new();
+        for &(y, x) in self.pawns() {
+            fields.push(x.to_string());
+            fields.push(y.to_string());
+        }
+        for num in &self.wall_nums[..self.config.player_num] {
+            fields.push(num.to_string());
+        }
+        let mut output = fields.join(" ");
+        output += "\n";
+
+        for y in 0..self.config.height - 1 {
+            for x in 0..self.config.width - 1 {
+                output += &format!(
+                    "{} ",
+                    match self.table.get(y as i8, x as i8) {
+                        Some((Dir::Horizontal, _)) => 1,
+                        Some((Dir::Vertical, _)) => 2,
+                        None => 0,
+                    }
+                );
+            }
+            output += "\n";
+        }
+        output
+    }
+
+    // README.md
+    /// Version 2 of the text sent to player `seat`, one `key values` line per field and
+    /// `end` after the last.
+    ///
+    /// `clocks` holds the time each player has used so far in milliseconds, in seat order.
+    pub fn socketformat_v2(&self, seat: usize, clocks: &[u64]) -> String {
+        let n = self.config.player_num;
+        let join = |v: Vec<String>| v.join(" ");
+        let mut lines = vec![
+            "state 2".to_string(),
+            format!("you {} {}", seat, Colour::from_index(seat)),
+            format!("turn {} {}", self.turn, Colour::from_index(self.turn)),
+            format!("ply {}", self.record.len()),
+        ];
+        lines.push(match self.record.last() {
+            Some(r) => format!(
+                "last {} {}",
+                Colour::from_index(self.prev_turn()),
+                r.command()
+            ),
+            None => "last none".to_string(),
+        });
+        lines.push(format!(
+            "pawns {}",
+            join(
+                self.pawns()
+                    .iter()
+                    .map(|&(y, x)| format!("{} {}", x, y))
+                    .collect()
+            )
+        ));
+        lines.push(format!(
+            "walls_left {}",
+            join(
+                self.wall_nums[..n]
+                    .iter()
+                    .map(|num| num.to_string())
+                    .collect()
+            )
+        ));
+        lines.push(format!(
+            "clock {}",
+            join(clocks.iter().map(|ms| ms.to_string()).collect())
+        ));
+        let walls: Vec<_> = self.table.walls().collect();
+        lines.push(format!("walls {}", walls.len()));
+        for ((y, x), dir, owner) in walls {
+            lines.push(format!("{} {} {} {}", x, y, dir, owner));
+        }
+        lines.push("end".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    /// Builds a position from the text [`Quoridor::socketformat`] writes, with player
+    /// `turn` to move.
+    ///
+    /// The format does not say who put each wall, so the walls are handed out in seat
+    /// order to the players who have used some. The number of walls on the board must
+    /// match the walls left in `config`, and every pawn must still be able to reach its goal.
+    pub fn from_socketformat(config: GameConfig, text: &str, turn: usize) -> Result<Self, String> {
+        config.validate()?;
+        let (h, w, n) = (config.height, config.width, config.player_num);
+        if turn >= n {
+            return Err(format!("Turn must be less than {}", n));
+        }
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines
+            .next()
+            .ok_or_else(|| "Position is empty".to_string())?
+            .split_whitespace()
+            .map(|s| {
+                s.parse::<usize>()
+                    .map_err(|_| format!("Invalid number {}", s))
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+        if header.len() != 3 * n {
+            return Err(format!("Expected {} numbers on the first line", 3 * n));
+        }
+
+        let mut q = Quoridor::new(config);
+        q.turn = turn;
+        for id in 0..n {
+            let colour = Colour::from_index(id);
+            let (x, y) = (header[2 * id], header[2 * id + 1]);
+            q.place_pawn(id, (y, x))?;
+            if config.on_side((y, x), config.goal(id)) {
+                return Err(format!("Pawn of {} is already on its goal", colour));
+            }
+            let num = header[2 * n + id];
+            if num > config.wall_limit {
+                return Err(format!(
+                    "{} cannot have more than {} walls",
+                    colour, config.wall_limit
+                ));
+            }
+            q.wall_nums[id] = num;
+        }
+
+        // 使った壁の数だけ手番順に持ち主を割り当てる
+        let mut used: Vec<usize> = (0..n)
+            .map(|id| config.wall_limit - q.wall_nums[id])
+            .collect();
+        let mut owner = 0;
+        for y in 0..h - 1 {
+            let row: Vec<&str> = lines
+                .next()
+                .ok_or_else(|| format!("Expected {} rows of walls", h - 1))?
+                .split_whitespace()
+                .collect();
+            if row.len() != w - 1 {
+                return Err(format!("Row {} of walls must have {} flags", y, w - 1));
+            }
+            for (x, flag) in row.into_iter().enumerate() {
+                let dir = match flag {
+                    "0" => continue,
+                    "1" => Dir::Horizontal,
+                    "2" => Dir::Vertical,
+                    _ => return Err(format!("Invalid wall flag {} at {} {}", flag, x, y)),
+                };
+                while owner < n && used[owner] == 0 {
+                    owner += 1;
+                }
+                if owner == n {
+                    return Err("More walls on the board than the players have put".to_string());
+                }
+                used[owner] -= 1;
+                q.place_wall((y, x), dir, owner)?;
+            }
+        }
+        if lines.next().is_some() {
+            return Err("Unexpected line after the walls".to_string());
+        }
+        if used.iter().any(|&num| num != 0) {
+            return Err("Fewer walls on the board than the players have put".to_string());
+        }
+        q.finish_setup()
+    }
+
+    /// Restores a position from the code [`Quoridor::viewformat`] writes.
+    ///
+    /// `config` has to be one `viewformat` supports. Each player has its wall limit
+    /// minus the walls of its colour left, and the record starts empty.
+    pub fn from_qfcode(config: GameConfig, code: &str) -> Result<Self, String> {
+        config.validate()?;
+        if config.height != 9
+            || config.width != 9
+            || config.player_num != 2
+            || 15 < config.wall_limit
+        {
+            return Err("qfcode only describes two-player games on a 9x9 board".to_string());
+        }
+        let mut reader = BitReader::from_base64(code.trim(), Alphabet::Standard)
+            .map_err(|e| format!("Invalid qfcode: {}", e))?;
+        let mut read = |width| {
+            reader
+                .read(width)
+                .map_err(|e| format!("Invalid qfcode: {}", e))
+        };
+        if read(2)? != 0b10 {
+            return Err("Not a qfcode position".to_string());
+        }
+
+        let mut q = Quoridor::new(config);
+        for id in 0..2 {
+            let square = u8_to_pos(&config, read(7)? as u8)?;
+            q.place_pawn(id, square)?;
+        }
+        // 白の横壁, 白の縦壁, 黒の横壁, 黒の縦壁の順
+        let mut used = [0; 2];
+        for &(id, dir) in &[
+            (0, Dir::Horizontal),
+            (0, Dir::Vertical),
+            (1, Dir::Horizontal),
+            (1, Dir::Vertical),
+        ] {
+            for _ in 0..read(4)? {
+                let centre = u8_to_wall(&config, read(6)? as u8)?;
+                q.place_wall(centre, dir, id)?;
+                used[id] += 1;
+            }
+        }
+        for (id, &num) in used.iter().enumerate() {
+            if num > config.wall_limit {
+                return Err(format!(
+                    "{} has put more than {} walls",
+                    Colour::from_index(id),
+                    config.wall_limit
+                ));
+            }
+            q.wall_nums[id] = config.wall_limit - num;
+        }
+        q.turn = if read(1)? == 1 { 0 } else { 1 };
+        if read(1)? == 1 {
+            q.last_move = Some(u8_to_wall(&config, read(6)? as u8)?);
+        }
+        q.turn_num = read(10)? as u16;
+        q.finish_setup()
+    }
+
+    /// Replays a game from the code [`Quoridor::historyformat`] writes.
+    ///
+    /// The code does not hold the game settings, so `config` has to match the game.
+    pub fn from_history(config: GameConfig, code: &str) -> Result<Self, String> {
+        config.validate()?;
+        if 64 < (config.height - 1) * (config.width - 1) {
+            return Err("History codes only describe boards up to 9x9".to_string());
+        }
+        let mut reader = BitReader::from_base64(code.trim(), Alphabet::Standard)
+            .map_err(|e| format!("Invalid history: {}", e))?;
+        let mut read = |width| {
+            reader
+                .read(width)
+                .map_err(|e| format!("Invalid history: {}", e))
+        };
+        if read(2)? != 0b01 {
+            return Err("Not a history code".to_string());
+        }
+
+        let mut q = Quoridor::new(config);
+        for ply in 1..=read(10)? {
+            let com = if read(1)? == 0 {
+                let d = read(3)? as u8;
+                let (dy, dx) = Record::to_dydx(d)
+                    .ok_or_else(|| format!("Ply {}: invalid move direction {}", ply, d))?;
+                // まっすぐ飛び越える手も1マスの移動と同じ向きで記録されている
+                let (y, x) = q.pawns[q.turn];
+                let (y, x) = q
+                    .next_moves()
+                    .into_iter()
+                    .find(|&(ty, tx)| {
+                        (ty as i8 - y as i8).signum() == dy && (tx as i8 - x as i8).signum() == dx
+                    })
+                    .ok_or_else(|| format!("Ply {}: no move in direction {}", ply, d))?;
+                Command::Move(y, x)
+            } else {
+                let dir = if read(1)? == 1 {
+                    Dir::Vertical
+                } else {
+                    Dir::Horizontal
+                };
+                let (y, x) = u8_to_wall(&config, read(6)? as u8)?;
+                Command::Put(y, x, dir)
+            };
+            q.play(&com).map_err(|e| format!("Ply {}: {}", ply, e))?;
+        }
+        Ok(q)
+    }
+
+    // 局面の読み込み用: 駒を置く
+    fn place_pawn(&mut self, id: usize, (y, x): (usize, usize)) -> Result<(), String> {
+        let colour = Colour::from_index(id);
+        if !self.config.in_area(y, x) {
+            return Err(format!("Pawn of {} is out of bounds", colour));
+        }
+        if self.pawns[..id].contains(&(y, x)) {
+            return Err(format!("Pawn of {} is on another pawn", colour));
+        }
+        self.pawns[id] = (y, x);
+        Ok(())
+    }
+
+    // 局面の読み込み用: 壁を置く
+    fn place_wall(&mut self, (y, x): (usize, usize), dir: Dir, owner: usize) -> Result<(), String> {
+        if !self.table.fits(y as i8, x as i8, dir) {
+            return Err(format!("Wall at {} {} overlaps another wall", x, y));
+        }
+        self.table
+            .set(y as i8, x as i8, dir, Colour::from_index(owner));
+        Ok(())
+    }
+
+    // 全員がゴールに行けることを確かめてハッシュを作り直す
+    fn finish_setup(mut self) -> Result<Self, String> {
+        for (id, &(y, x)) in self.pawns().iter().enumerate() {
+            if self.table.distance(y, x, self.config.goal(id)).is_none() {
+                return Err(format!("{} cannot reach its goal", Colour::from_index(id)));
+            }
+        }
+        self.hash = self.compute_hash();
+        self.hashes = vec![self.hash];
+        self.custom_start = true;
+        Ok(self)
+    }
+
+    // https://www.quoridorfansite.com/tools/qfb.html
+    /// Position code understood by the quoridorfansite viewer.
+    ///
+    /// Returns `None` unless the game is a two-player game on a 9x9 board, or once the
+    /// turn number no longer fits the format.
+    pub fn viewformat(&self) -> Option<String> {
+        let config = &self.config;
+        if config.height != 9
+            || config.width != 9
+            || config.player_num != 2
+            || 15 < config.wall_limit
+            || 1024 <= self.turn_num
+        {
+            return None;
+        }
+        let mut w = BitWriter::new();
+        w.write(0b10, 2);
+        w.write(pos_to_u8(config, self.pawns[0]) as u64, 7);
+        w.write(pos_to_u8(config, self.pawns[1]) as u64, 7);
+        // 白の横壁, 白の縦壁, 黒の横壁, 黒の縦壁の順
+        for &(colour, dir) in &[
+            (Colour::White, Dir::Horizontal),
+            (Colour::White, Dir::Vertical),
+            (Colour::Black, Dir::Horizontal),
+            (Colour::Black, Dir::Vertical),
+        ] {
+            let walls: Vec<(usize, usize)> = self
+                .table
+                .walls()
+                .filter(|&(_, d, c)| d == dir && c == colour)
+                .map(|(pos, _, _)| pos)
+                .collect();
+            w.write(walls.len() as u64, 4);
+            for pos in walls {
+                w.write(wall_to_u8(config, pos) as u64, 6);
+            }
+        }
+        w.write_bool(self.turn == 0);
+        w.write_bool(self.last_move.is_some());
+        if let Some(pos) = self.last_move {
+            w.write(wall_to_u8(config, pos) as u64, 6);
+        }
+        w.write(self.turn_num as u64, 10);
+        Some(w.to_base64(Alphabet::Standard))
+    }
+
+    /// Base64 bit-packed list of every ply, written at the end of a game.
+    ///
+    /// Returns `None` if the game did not start from the initial position, or if the wall
+    /// centres or the number of plies do not fit the format.
+    pub fn historyformat(&self) -> Option<String> {
+        let config = &self.config;
+        if self.custom_start
+            || 64 < (config.height - 1) * (config.width - 1)
+            || 1024 <= self.record.len()
+        {
+            return None;
+        }
+        let mut w = BitWriter::new();
+        w.write(0b01, 2);
+        w.write(self.record.len() as u64, 10);
+        for com in &self.record {
+            match com {
+                Record::Piece(..) => {
+                    w.write_bool(false);
+                    w.write(com.movedir().unwrap() as u64, 3);
+                }
+                Record::Wall(y, x, dir) => {
+                    w.write_bool(true);
+                    w.write_bool(*dir == Dir::Vertical);
+                    w.write(wall_to_u8(config, (*y, *x)) as u64, 6);
+                }
+            }
+        }
+        Some(w.to_base64(Alphabet::Standard))
+    }
+}
+
+impl Default for Quoridor {
+    fn default() -> Self {
+        Self::new(GameConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testutil::{Position, Rng};
+
+    // 2人と4人，5x5 から 11x11 までの盤
+    fn configs() -> Vec<GameConfig> {
+        let mut configs = Vec::new();
+        for &size in &[5, 7, 9, 11] {
+            for &players in &[2, 4] {
+                configs.push(GameConfig::new(size, size + 1, players).unwrap());
+            }
+        }
+        configs
+    }
+
+    #[test]
+    fn legal_walls_match_settable() {
+        let mut rng = Rng::new(6);
+        for config in configs() {
+            for _ in 0..3 {
+                let mut q = Quoridor::new(config);
+                while q.is_over().is_none() && q.record().len() < 80 {
+                    let mut expected = Vec::new();
+                    for y in 0..config.height - 1 {
+                        for x in 0..config.width - 1 {
+                            for &dir in &[Dir::Horizontal, Dir::Vertical] {
+                                if q.settable(y, x, dir).is_ok() {
+                                    expected.push((y, x, dir));
+                                }
+                            }
+                        }
+                    }
+                    assert_eq!(q.legal_walls(), expected);
+                    let com = rng.action(&q);
+                    q.play(&com).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hash_and_undo_keep_the_position() {
+        let mut rng = Rng::new(9);
+        for config in configs() {
+            for _ in 0..3 {
+                let mut q = Quoridor::new(config);
+                let mut positions = vec![(Position::of(&q), q.hash())];
+                while q.is_over().is_none() && q.record().len() < 80 {
+                    // 合法手をすべて apply と unapply で試す
+                    let (before, hash) = (Position::of(&q), q.hash());
+                    for com in q.legal_actions() {
+                        let undo = q.apply(com);
+                        assert_eq!(q.hash(), q.compute_hash());
+                        q.unapply(undo);
+                        assert_eq!(Position::of(&q), before);
+                        assert_eq!(q.hash(), hash);
+                    }
+                    let com = rng.action(&q);
+                    q.play(&com).unwrap();
+                    assert_eq!(q.hash(), q.compute_hash());
+                    positions.push((Position::of(&q), q.hash()));
+                }
+                // undo で一手ずつ戻る
+                positions.pop();
+                while let Some((position, hash)) = positions.pop() {
+                    assert!(q.undo());
+                    assert_eq!(Position::of(&q), position);
+                    assert_eq!(q.hash(), hash);
+                }
+                assert!(!q.undo());
+            }
+        }
+    }
+
+    // 跳んでから戻すと元の局面に戻り，棋譜からも同じ記録が読めること
+    fn check_jump(q: &mut Quoridor, to: (usize, usize)) {
+        let before = Position::of(q);
+        let hash = q.hash();
+        let from = q.pawn(q.turn());
+        q.play(&Command::Move(to.0, to.1)).unwrap();
+        assert_eq!(q.record().last(), Some(&Record::Piece(from, to)));
+        let code = q.historyformat().unwrap();
+        let replayed = Quoridor::from_history(*q.config(), &code).unwrap();
+        assert_eq!(replayed.record(), q.record());
+        assert_eq!(Position::of(&replayed), Position::of(q));
+        assert!(q.undo());
+        assert_eq!(Position::of(q), before);
+        assert_eq!(q.hash(), hash);
+    }
+
+    #[test]
+    fn undo_after_jumps() {
+        let mut q = Quoridor::default();
+        for com in &[
+            Command::Move(7, 4),
+            Command::Move(1, 4),
+            Command::Move(6, 4),
+            Command::Move(2, 4),
+            Command::Move(5, 4),
+            Command::Move(3, 4),
+            Command::Put(7, 0, Dir::Horizontal),
+            Command::Move(4, 4),
+        ] {
+            q.play(com).unwrap();
+        }
+        // 白 (5, 4) の正面に黒 (4, 4)
+        check_jump(&mut q, (3, 4));
+
+        // 黒の後ろに壁があると斜めにしか跳べない
+        q.play(&Command::Put(3, 4, Dir::Horizontal)).unwrap();
+        q.play(&Command::Put(0, 7, Dir::Horizontal)).unwrap();
+        assert_eq!(q.movable(3, 4), Err(IllegalAction::JumpBlocked(3, 4)));
+        check_jump(&mut q, (4, 3));
+        check_jump(&mut q, (4, 5));
+    }
+
+    #[test]
+    fn qfcode_round_trip() {
+        let config = GameConfig::default();
+        let mut rng = Rng::new(14);
+        for _ in 0..20 {
+            let mut q = Quoridor::new(config);
+            while q.is_over().is_none() && q.record().len() < 150 {
+                let code = q.viewformat().unwrap();
+                let loaded = Quoridor::from_qfcode(config, &code).unwrap();
+                assert_eq!(Position::of(&loaded), Position::of(&q), "{}", code);
+                assert_eq!(loaded.hash(), q.hash());
+                let com = rng.action(&q);
+                q.play(&com).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn qfcode_turn_limit() {
+        // 手数は10ビットで書く
+        let mut q = Quoridor::new(GameConfig::default());
+        q.turn_num = 1023;
+        let loaded = Quoridor::from_qfcode(*q.config(), &q.viewformat().unwrap()).unwrap();
+        assert_eq!(loaded.turn_num(), 1023);
+        q.turn_num = 1024;
+        assert_eq!(q.viewformat(), None);
+    }
+
+    #[test]
+    fn qfcode_errors() {
+        let config = GameConfig::default();
+        let code = Quoridor::new(config).viewformat().unwrap();
+        assert!(Quoridor::from_qfcode(config, &code[..3]).is_err());
+        assert!(Quoridor::from_qfcode(config, "g*").is_err());
+        // 棋譜のコードは先頭の2ビットが違う
+        let history = Quoridor::new(config).historyformat().unwrap();
+        assert_eq!(
+            Quoridor::from_qfcode(config, &history).err().unwrap(),
+            "Not a qfcode position"
+        );
+        let small = GameConfig::new(5, 3, 2).unwrap();
+        assert!(Quoridor::from_qfcode(small, &code).is_err());
+    }
+
+    #[test]
+    fn socketformat_v2() {
+        let config = GameConfig::default();
+        let mut q = Quoridor::new(config);
+        assert_eq!(
+            q.socketformat_v2(1, &[0, 0]),
+            "state 2\nyou 1 black\nturn 0 white\nply 0\nlast none\npawns 4 8 4 0\n\
+             walls_left 10 10\nclock 0 0\nwalls 0\nend\n"
+        );
+        for text in &["d7h", "a1v", "e2"] {
+            q.play(&Command::parse_notation(&config, text).unwrap())
+                .unwrap();
+        }
+        assert_eq!(
+            q.socketformat_v2(0, &[1500, 20]),
+            "state 2\nyou 0 white\nturn 1 black\nply 3\nlast white 4 7\npawns 4 7 4 0\n\
+             walls_left 9 9\nclock 1500 20\nwalls 2\n3 1 H white\n0 7 V black\nend\n"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use quoridor::{Quoridor, Record};
+use wall::{Colour, Dir};
+
+/// Characters [`Quoridor::render`] draws with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Charset {
+    /// The characters of [`Quoridor::display`].
+    Ascii,
+    /// Box-drawing characters.
+    Unicode,
+}
+
+/// How [`Quoridor::render`] draws the board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    pub charset: Charset,
+    /// Colour pawns and walls by owner with ANSI escapes and highlight the last action.
+    pub ansi: bool,
+    /// Protocol coordinates, `x` along the top and `y` down the left.
+    pub labels: bool,
+    /// Walls left, side to move and last action under the board.
+    pub status: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            charset: Charset::Ascii,
+            ansi: false,
+            labels: true,
+            status: true,
+        }
+    }
+}
+
+struct Glyphs {
+    horizontal: char,
+    vertical: char,
+    post: char,
+    // 左上, 右上, 左下, 右下
+    corners: [char; 4],
+    edge_h: char,
+    edge_v: char,
+    // 最後に動いた駒の元のマス
+    from: char,
+}
+
+const ASCII: Glyphs = Glyphs {
+    horizontal: '-',
+    vertical: '|',
+    post: '*',
+    corners: ['#'; 4],
+    edge_h: '#',
+    edge_v: '#',
+    from: '.',
+};
+
+const UNICODE: Glyphs = Glyphs {
+    horizontal: '━',
+    vertical: '┃',
+    post: '·',
+    corners: ['┌', '┐', '└', '┘'],
+    edge_h: '─',
+    edge_v: '│',
+    from: '∙',
+};
+
+const PAWNS: [char; 4] = ['W', 'B', 'R', 'G'];
+
+fn ansi_code(c: Colour) -> &'static str {
+    match c {
+        Colour::White => "1;37",
+        Colour::Black => "1;34",
+        Colour::Red => "1;31",
+        Colour::Green => "1;32",
+    }
+}
+
+impl Quoridor {
+    /// Board drawing for terminals. [`Quoridor::display`] stays the plain form for logs.
+    pub fn render(&self, opts: &RenderOptions) -> String {
+        let config = self.config();
+        let (h, w) = (config.height, config.width);
+        let g = match opts.charset {
+            Charset::Ascii => &ASCII,
+            Charset::Unicode => &UNICODE,
+        };
+        let paint = |c: char, code: Option<String>| match code {
+            Some(code) if opts.ansi => format!("\x1b[{}m{}\x1b[0m", code, c),
+            _ => c.to_string(),
+        };
+        let last = self.record().last().cloned();
+
+        // マスは偶数行・偶数列，壁は奇数行か奇数列
+        let mut cells: Vec<Vec<(char, Option<String>)>> =
+            vec![vec![(' ', None); 2 * w - 1]; 2 * h - 1];
+        for i in 0..h - 1 {
+            for j in 0..w - 1 {
+                cells[2 * i + 1][2 * j + 1] = (g.post, None);
+            }
+        }
+        for ((i, j), dir, colour) in self.table().walls() {
+            let mut code = ansi_code(colour).to_string();
+            if last == Some(Record::Wall(i, j, dir)) {
+                code += ";7";
+            }
+            let (ch, parts) = match dir {
+                Dir::Vertical => (
+                    g.vertical,
+                    [
+                        (2 * i, 2 * j + 1),
+                        (2 * i + 1, 2 * j + 1),
+                        (2 * i + 2, 2 * j + 1),
+                    ],
+                ),
+                Dir::Horizontal => (
+                    g.horizontal,
+                    [
+                        (2 * i + 1, 2 * j),
+                        (2 * i + 1, 2 * j + 1),
+                        (2 * i + 1, 2 * j + 2),
+                    ],
+                ),
+            };
+            for (k, &(y, x)) in parts.iter().enumerate() {
+                // ASCII では display と同じく壁の中心を残す
+                if k == 1 && opts.charset == Charset::Ascii {
+                    continue;
+                }
+                cells[y][x] = (ch, Some(code.clone()));
+            }
+        }
+        if let Some(Record::Piece((y, x), _)) = last {
+            cells[2 * y][2 * x] = (g.from, None);
+        }
+        for (id, &(y, x)) in self.pawns().iter().enumerate() {
+            let mut code = ansi_code(Colour::from_index(id)).to_string();
+            if let Some(Record::Piece(_, to)) = last {
+                if to == (y, x) {
+                    code += ";7";
+                }
+            }
+            cells[2 * y][2 * x] = (PAWNS[id], Some(code));
+        }
+
+        let margin = if opts.labels {
+            (h - 1).to_string().len() + 1
+        } else {
+            0
+        };
+        let mut s = String::new();
+        if opts.labels {
+            let xs: Vec<String> = (0..w).map(|x| format!("{:<2}", x)).collect();
+            s += &format!("{:1$}{2}\n", "", margin + 1, xs.concat().trim_end());
+        }
+        let edge: String = (0..2 * w - 1).map(|_| g.edge_h).collect();
+        s += &format!(
+            "{:1$}{2}{3}{4}\n",
+            "", margin, g.corners[0], edge, g.corners[1]
+        );
+        for (r, row) in cells.into_iter().enumerate() {
+            if opts.labels && r % 2 == 0 {
+                s += &format!("{:>1$} ", r / 2, margin - 1);
+            } else {
+                s += &format!("{:1$}", "", margin);
+            }
+            s.push(g.edge_v);
+            for (c, code) in row {
+                s += &paint(c, code);
+            }
+            s.push(g.edge_v);
+            s += "\n";
+        }
+        s += &format!(
+            "{:1$}{2}{3}{4}\n",
+            "", margin, g.corners[2], edge, g.corners[3]
+        );
+
+        if opts.status {
+            let walls: Vec<String> = (0..config.player_num)
+                .map(|id| {
+                    let colour = Colour::from_index(id);
+                    format!(
+                        "{} {}: {} walls",
+                        paint(PAWNS[id], Some(ansi_code(colour).to_string())),
+                        colour,
+                        self.wall_num(id)
+                    )
+                })
+                .collect();
+            s += &walls.join(", ");
+            s += "\n";
+            match self.is_over() {
+                Some(outcome) => s += &format!("{}\n", outcome),
+                None => {
+                    s += &format!(
+                        "ply {}, {} to move\n",
+                        self.record().len() + 1,
+                        Colour::from_index(self.turn())
+                    )
+                }
+            }
+            if let Some(r) = last {
+                let com = r.command();
+                s += &format!("last: {} ({})\n", com, com.to_notation(config));
+            }
+        }
+        s
+    }
+}
@@ -0,0 +1,200 @@
+use quoridor::{Command, Quoridor, Record};
+use wall::{Colour, Dir};
+
+/// What [`Quoridor::svg`] draws besides the position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Protocol coordinates, `x` along the top and `y` down the left.
+    pub labels: bool,
+    /// Arrow for the last pawn move, or an outline around the last wall.
+    pub last_move: bool,
+    /// Actions to draw as numbered arrows and outlines from the current position,
+    /// such as a line a bot is considering. Drawing stops at the first illegal one.
+    pub line: Vec<Command>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            labels: true,
+            last_move: true,
+            line: Vec::new(),
+        }
+    }
+}
+
+// マスの大きさと溝の幅
+const CELL: usize = 40;
+const GAP: usize = 10;
+const PITCH: usize = CELL + GAP;
+const MARGIN: usize = 30;
+
+const LAST_MOVE: &str = "#e07000";
+const LINE: &str = "#1060d0";
+
+// 駒と壁の塗り, 縁取り
+fn colours(c: Colour) -> (&'static str, &'static str) {
+    match c {
+        Colour::White => ("#f8f8f8", "#333333"),
+        Colour::Black => ("#303030", "#000000"),
+        Colour::Red => ("#d83030", "#801010"),
+        Colour::Green => ("#30a040", "#105020"),
+    }
+}
+
+// マス (y, x) の中心
+fn centre((y, x): (usize, usize)) -> (usize, usize) {
+    (MARGIN + x * PITCH + CELL / 2, MARGIN + y * PITCH + CELL / 2)
+}
+
+// 中心 (y, x) の壁の矩形 (左上の x, y, 幅, 高さ)
+fn wall_rect(y: usize, x: usize, dir: Dir) -> (usize, usize, usize, usize) {
+    match dir {
+        Dir::Horizontal => (
+            MARGIN + x * PITCH,
+            MARGIN + y * PITCH + CELL,
+            2 * CELL + GAP,
+            GAP,
+        ),
+        Dir::Vertical => (
+            MARGIN + x * PITCH + CELL,
+            MARGIN + y * PITCH,
+            GAP,
+            2 * CELL + GAP,
+        ),
+    }
+}
+
+fn arrow(s: &mut String, from: (usize, usize), to: (usize, usize), colour: &str, marker: &str) {
+    let ((x1, y1), (x2, y2)) = (centre(from), centre(to));
+    s.push_str(&format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"4\" marker-end=\"url(#{})\"/>\n",
+        x1, y1, x2, y2, colour, marker
+    ));
+}
+
+fn outline(s: &mut String, (y, x): (usize, usize), dir: Dir, colour: &str, dashed: bool) {
+    let (rx, ry, w, h) = wall_rect(y, x, dir);
+    s.push_str(&format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"3\"{}/>\n",
+        rx - 3,
+        ry - 3,
+        w + 6,
+        h + 6,
+        colour,
+        if dashed { " stroke-dasharray=\"6 3\"" } else { "" }
+    ));
+}
+
+impl Quoridor {
+    /// SVG image of the position, with pawns and walls in their owners' colours.
+    pub fn svg(&self, opts: &SvgOptions) -> String {
+        let config = self.config();
+        let (h, w) = (config.height, config.width);
+        let width = 2 * MARGIN + w * PITCH - GAP;
+        let height = 2 * MARGIN + h * PITCH - GAP;
+        let mut s = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\">\n",
+            width, height
+        );
+        s += "<defs>\n";
+        for &(id, colour) in &[("last", LAST_MOVE), ("line", LINE)] {
+            s += &format!(
+                "<marker id=\"{}\" viewBox=\"0 0 10 10\" refX=\"8\" refY=\"5\" markerWidth=\"4\" markerHeight=\"4\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\" fill=\"{}\"/></marker>\n",
+                id, colour
+            );
+        }
+        s += "</defs>\n";
+        s += &format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"#8b5a2b\"/>\n",
+            width, height
+        );
+        for y in 0..h {
+            for x in 0..w {
+                s += &format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#deb887\"/>\n",
+                    MARGIN + x * PITCH,
+                    MARGIN + y * PITCH,
+                    CELL,
+                    CELL
+                );
+            }
+        }
+        if opts.labels {
+            for x in 0..w {
+                s += &format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"14\" text-anchor=\"middle\" fill=\"#ffffff\">{}</text>\n",
+                    centre((0, x)).0,
+                    MARGIN - 10,
+                    x
+                );
+            }
+            for y in 0..h {
+                s += &format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"14\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"#ffffff\">{}</text>\n",
+                    MARGIN / 2,
+                    centre((y, 0)).1,
+                    y
+                );
+            }
+        }
+
+        for ((y, x), dir, colour) in self.table().walls() {
+            let (fill, stroke) = colours(colour);
+            let (rx, ry, w, h) = wall_rect(y, x, dir);
+            s += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"{}\"/>\n",
+                rx, ry, w, h, fill, stroke
+            );
+        }
+        for (id, &pos) in self.pawns().iter().enumerate() {
+            let (fill, stroke) = colours(Colour::from_index(id));
+            let (cx, cy) = centre(pos);
+            s += &format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+                cx,
+                cy,
+                CELL / 2 - 5,
+                fill,
+                stroke
+            );
+        }
+
+        if opts.last_move {
+            match self.record().last() {
+                Some(&Record::Piece(from, to)) => arrow(&mut s, from, to, LAST_MOVE, "last"),
+                Some(&Record::Wall(y, x, dir)) => outline(&mut s, (y, x), dir, LAST_MOVE, false),
+                None => {}
+            }
+        }
+
+        // 読み筋は複製した局面で実際に指しながら描く
+        let mut game = self.clone();
+        for (i, com) in opts.line.iter().enumerate() {
+            let from = game.pawn(game.turn());
+            if game.play(com).is_err() {
+                break;
+            }
+            let (tx, ty) = match *com {
+                Command::Move(y, x) => {
+                    arrow(&mut s, from, (y, x), LINE, "line");
+                    centre((y, x))
+                }
+                Command::Put(y, x, dir) => {
+                    outline(&mut s, (y, x), dir, LINE, true);
+                    let (rx, ry, w, h) = wall_rect(y, x, dir);
+                    (rx + w / 2, ry + h / 2)
+                }
+            };
+            s += &format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"13\" font-weight=\"bold\" fill=\"{}\">{}</text>\n",
+                tx + 6,
+                ty - 6,
+                LINE,
+                i + 1
+            );
+        }
+        s += "</svg>\n";
+        s
+    }
+}
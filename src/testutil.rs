@@ -0,0 +1,68 @@
+//! Helpers shared by the unit tests.
+use quoridor::{Command, Quoridor};
+use wall::{Colour, Dir};
+
+/// Xorshift generator, so the randomized tests are repeatable without extra crates.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A random legal action, a pawn move half of the time so games make progress.
+    pub fn action(&mut self, q: &Quoridor) -> Command {
+        let actions = q.legal_actions();
+        let moves = actions
+            .iter()
+            .take_while(|com| match com {
+                Command::Move(..) => true,
+                Command::Put(..) => false,
+            })
+            .count();
+        if self.below(2) == 0 {
+            actions[self.below(moves)]
+        } else {
+            actions[self.below(actions.len())]
+        }
+    }
+}
+
+/// Everything that decides how a position plays on, for comparing positions reached
+/// in different ways.
+#[derive(Debug, PartialEq)]
+pub struct Position {
+    pawns: Vec<(usize, usize)>,
+    walls: Vec<((usize, usize), Dir, Colour)>,
+    wall_nums: Vec<usize>,
+    turn: usize,
+    last_move: Option<(usize, usize)>,
+    turn_num: u16,
+}
+
+impl Position {
+    pub fn of(q: &Quoridor) -> Self {
+        Position {
+            pawns: q.pawns().to_vec(),
+            walls: q.table().walls().collect(),
+            wall_nums: (0..q.config().player_num)
+                .map(|id| q.wall_num(id))
+                .collect(),
+            turn: q.turn(),
+            last_move: q.last_move(),
+            turn_num: q.turn_num(),
+        }
+    }
+}
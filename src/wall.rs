@@ -0,0 +1,384 @@
+use config::{Side, MAX_SIZE};
+use std::fmt;
+use DPOS;
+
+/// Owner of a wall or pawn, one per seat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Colour {
+    White,
+    Black,
+    Red,
+    Green,
+}
+
+impl Colour {
+    pub fn from_index(id: usize) -> Self {
+        [Colour::White, Colour::Black, Colour::Red, Colour::Green][id]
+    }
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Colour::White => "white",
+            Colour::Black => "black",
+            Colour::Red => "red",
+            Colour::Green => "green",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Orientation of a wall.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dir {
+    Vertical,
+    Horizontal,
+}
+
+impl Dir {
+    /// Parses `"V"` or `"H"`.
+    pub fn parse(input: &str) -> Option<Self> {
+        match input {
+            "V" => Some(Dir::Vertical),
+            "H" => Some(Dir::Horizontal),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Dir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dir::Vertical => write!(f, "V"),
+            Dir::Horizontal => write!(f, "H"),
+        }
+    }
+}
+
+/// A set of squares, bit `y * width + x`.
+pub type Bitboard = u128;
+
+// DPOS の順 (上, 左, 下, 右)
+const UP: usize = 0;
+const LEFT: usize = 1;
+const DOWN: usize = 2;
+const RIGHT: usize = 3;
+
+fn dir_index(dy: i8, dx: i8) -> usize {
+    match (dy, dx) {
+        (-1, 0) => UP,
+        (0, -1) => LEFT,
+        (1, 0) => DOWN,
+        _ => RIGHT,
+    }
+}
+
+/// Walls on the board, indexed by the wall centre `(y, x)`.
+///
+/// Walls are kept as bit masks over the wall centres (bit `y * (width - 1) + x`), together
+/// with the squares whose step in each direction they block, so copying the table and
+/// searching it never allocates.
+#[derive(Clone, Copy)]
+pub struct WallTable {
+    height: usize,
+    width: usize,
+    horizontal: u128,
+    vertical: u128,
+    // 壁の持ち主の番号を2ビットで持つ
+    owner: [u128; 2],
+    // DPOS の各方向について，壁で移動できないマス
+    blocked: [Bitboard; 4],
+    // DPOS の各方向について，盤の端にあるマス
+    edges: [Bitboard; 4],
+}
+
+impl WallTable {
+    /// Whether `(y, x)` is a square of the board.
+    pub fn in_area(&self, y: usize, x: usize) -> bool {
+        y < self.height && x < self.width
+    }
+    /// Whether `(y, x)` is a wall centre of the board.
+    pub fn in_wall_area(&self, y: i8, x: i8) -> bool {
+        0 <= y && y < (self.height - 1) as i8 && 0 <= x && x < (self.width - 1) as i8
+    }
+
+    fn wall_bit(&self, y: i8, x: i8) -> u128 {
+        1 << (y as usize * (self.width - 1) + x as usize)
+    }
+    /// Bit of square `(y, x)`.
+    pub fn square_bit(&self, y: usize, x: usize) -> Bitboard {
+        1 << (y * self.width + x)
+    }
+
+    pub fn get(&self, y: i8, x: i8) -> Option<(Dir, Colour)> {
+        if !self.in_wall_area(y, x) {
+            return None;
+        }
+        let bit = self.wall_bit(y, x);
+        let dir = if self.horizontal & bit != 0 {
+            Dir::Horizontal
+        } else if self.vertical & bit != 0 {
+            Dir::Vertical
+        } else {
+            return None;
+        };
+        let id = (self.owner[0] & bit != 0) as usize | ((self.owner[1] & bit != 0) as usize) << 1;
+        Some((dir, Colour::from_index(id)))
+    }
+    /// Removes the wall centred on `(y, x)`, if any.
+    ///
+    /// Legal walls never share a segment, so only the steps this wall blocked are freed.
+    pub fn erase(&mut self, y: i8, x: i8) {
+        let dir = match self.get(y, x) {
+            Some((dir, _)) => dir,
+            None => return,
+        };
+        let bit = !self.wall_bit(y, x);
+        self.horizontal &= bit;
+        self.vertical &= bit;
+        self.owner[0] &= bit;
+        self.owner[1] &= bit;
+        let cells = self.cut_squares(y as usize, x as usize, dir);
+        match dir {
+            Dir::Horizontal => {
+                self.blocked[DOWN] &= !cells;
+                self.blocked[UP] &= !(cells << self.width);
+            }
+            Dir::Vertical => {
+                self.blocked[RIGHT] &= !cells;
+                self.blocked[LEFT] &= !(cells << 1);
+            }
+        }
+    }
+    pub fn set(&mut self, y: i8, x: i8, dir: Dir, c: Colour) {
+        let bit = self.wall_bit(y, x);
+        match dir {
+            Dir::Horizontal => self.horizontal |= bit,
+            Dir::Vertical => self.vertical |= bit,
+        }
+        let id = c.index();
+        if id & 1 != 0 {
+            self.owner[0] |= bit;
+        }
+        if id & 2 != 0 {
+            self.owner[1] |= bit;
+        }
+        self.block(y as usize, x as usize, dir);
+    }
+    fn block(&mut self, y: usize, x: usize, dir: Dir) {
+        let cells = self.cut_squares(y, x, dir);
+        match dir {
+            Dir::Horizontal => {
+                self.blocked[DOWN] |= cells;
+                self.blocked[UP] |= cells << self.width;
+            }
+            Dir::Vertical => {
+                self.blocked[RIGHT] |= cells;
+                self.blocked[LEFT] |= cells << 1;
+            }
+        }
+    }
+    pub fn new(height: usize, width: usize) -> Self {
+        let mut edges = [0; 4];
+        for x in 0..width {
+            edges[UP] |= 1 << x;
+            edges[DOWN] |= 1 << ((height - 1) * width + x);
+        }
+        for y in 0..height {
+            edges[LEFT] |= 1 << (y * width);
+            edges[RIGHT] |= 1 << (y * width + width - 1);
+        }
+        WallTable {
+            height,
+            width,
+            horizontal: 0,
+            vertical: 0,
+            owner: [0; 2],
+            blocked: [0; 4],
+            edges,
+        }
+    }
+
+    /// Every wall as `((y, x), dir, owner)`, top to bottom.
+    pub fn walls(&self) -> Walls {
+        Walls {
+            table: *self,
+            next: 0,
+        }
+    }
+
+    /// Squares on `side` of the board.
+    pub fn side_mask(&self, side: Side) -> Bitboard {
+        match side {
+            Side::Top => self.edges[UP],
+            Side::Bottom => self.edges[DOWN],
+            Side::Left => self.edges[LEFT],
+            Side::Right => self.edges[RIGHT],
+        }
+    }
+
+    /// Squares of `set` together with every square one step away from them.
+    pub fn expand(&self, set: Bitboard) -> Bitboard {
+        let free = |d: usize| set & !(self.blocked[d] | self.edges[d]);
+        set | free(UP) >> self.width | free(DOWN) << self.width | free(LEFT) >> 1 | free(RIGHT) << 1
+    }
+
+    /// Whether the pawn on `(y, x)` can step by `(dy, dx)` without leaving the board
+    /// or crossing a wall.
+    pub fn can_step(&self, y: i8, x: i8, dy: i8, dx: i8) -> bool {
+        let d = dir_index(dy, dx);
+        let bit = self.square_bit(y as usize, x as usize);
+        (self.blocked[d] | self.edges[d]) & bit == 0
+    }
+
+    pub fn checkwalldir(&self, y: i8, x: i8, dir: Dir) -> bool {
+        if let Some((d, _)) = self.get(y, x) {
+            d == dir
+        } else {
+            false
+        }
+    }
+
+    /// Whether a `dir` wall centred on `(y, x)` neither overlaps nor crosses another wall.
+    pub fn fits(&self, y: i8, x: i8, dir: Dir) -> bool {
+        if !self.in_wall_area(y, x) || self.get(y, x).is_some() {
+            return false;
+        }
+        match dir {
+            Dir::Horizontal => {
+                !self.checkwalldir(y, x - 1, Dir::Horizontal)
+                    && !self.checkwalldir(y, x + 1, Dir::Horizontal)
+            }
+            Dir::Vertical => {
+                !self.checkwalldir(y - 1, x, Dir::Vertical)
+                    && !self.checkwalldir(y + 1, x, Dir::Vertical)
+            }
+        }
+    }
+
+    /// Squares a `dir` wall centred on `(y, x)` would cut off from the square below
+    /// (horizontal) or to the right (vertical).
+    pub fn cut_squares(&self, y: usize, x: usize, dir: Dir) -> Bitboard {
+        match dir {
+            Dir::Horizontal => self.square_bit(y, x) | self.square_bit(y, x + 1),
+            Dir::Vertical => self.square_bit(y, x) | self.square_bit(y + 1, x),
+        }
+    }
+
+    /// Whether a wall blocks the step from `(y, x)` by `(dy, dx)`.
+    pub fn exist_wall(&self, y: i8, x: i8, dy: i8, dx: i8) -> bool {
+        if !self.in_area(y as usize, x as usize) {
+            return false;
+        }
+        self.blocked[dir_index(dy, dx)] & self.square_bit(y as usize, x as usize) != 0
+    }
+
+    /// Number of steps from `(y, x)` to the `goal` edge ignoring pawns, or `None` if the
+    /// edge is sealed off.
+    pub fn distance(&self, y: usize, x: usize, goal: Side) -> Option<usize> {
+        let goal = self.side_mask(goal);
+        let mut seen = self.square_bit(y, x);
+        let mut d = 0;
+        loop {
+            if seen & goal != 0 {
+                return Some(d);
+            }
+            let next = self.expand(seen);
+            if next == seen {
+                return None;
+            }
+            seen = next;
+            d += 1;
+        }
+    }
+
+    /// One shortest route from `(y, x)` to the `goal` edge ignoring pawns, both ends included.
+    pub fn shortest_path(&self, y: usize, x: usize, goal: Side) -> Option<Vec<(usize, usize)>> {
+        let goal = self.side_mask(goal);
+        // layers[d]: ちょうど d 手で届くマス
+        let mut layers = [0 as Bitboard; MAX_SIZE * MAX_SIZE];
+        layers[0] = self.square_bit(y, x);
+        let mut seen = layers[0];
+        let mut d = 0;
+        while seen & goal == 0 {
+            let next = self.expand(seen);
+            if next == seen {
+                return None;
+            }
+            d += 1;
+            layers[d] = next & !seen;
+            seen = next;
+        }
+
+        let mut cur = (layers[d] & goal).trailing_zeros() as usize;
+        let mut path = vec![(cur / self.width, cur % self.width)];
+        for layer in layers[..d].iter().rev() {
+            let (cy, cx) = (cur / self.width, cur % self.width);
+            for &(dy, dx) in DPOS.iter() {
+                if !self.can_step(cy as i8, cx as i8, dy, dx) {
+                    continue;
+                }
+                let (ny, nx) = ((cy as i8 + dy) as usize, (cx as i8 + dx) as usize);
+                if layer & self.square_bit(ny, nx) != 0 {
+                    cur = ny * self.width + nx;
+                    path.push((ny, nx));
+                    break;
+                }
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Distance to the `goal` edge from every square ignoring pawns, indexed `[y][x]`.
+    pub fn distance_map(&self, goal: Side) -> Vec<Vec<Option<usize>>> {
+        let mut map = vec![vec![None; self.width]; self.height];
+        // 壁は両方向をふさぐので，ゴールから広げた距離がそのまま使える
+        let mut layer = self.side_mask(goal);
+        let mut seen = layer;
+        let mut d = 0;
+        while layer != 0 {
+            for (y, row) in map.iter_mut().enumerate() {
+                for (x, cell) in row.iter_mut().enumerate() {
+                    if layer & self.square_bit(y, x) != 0 {
+                        *cell = Some(d);
+                    }
+                }
+            }
+            let next = self.expand(seen);
+            layer = next & !seen;
+            seen = next;
+            d += 1;
+        }
+        map
+    }
+}
+
+/// Iterator returned by [`WallTable::walls`].
+pub struct Walls {
+    table: WallTable,
+    next: usize,
+}
+
+impl Iterator for Walls {
+    type Item = ((usize, usize), Dir, Colour);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let w = self.table.width - 1;
+        let occupied = self.table.horizontal | self.table.vertical;
+        while self.next < (self.table.height - 1) * w {
+            let i = self.next;
+            self.next += 1;
+            if occupied & (1 << i) != 0 {
+                let (y, x) = (i / w, i % w);
+                let (dir, c) = self.table.get(y as i8, x as i8).unwrap();
+                return Some(((y, x), dir, c));
+            }
+        }
+        None
+    }
+}
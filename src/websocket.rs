@@ -3,19 +3,21 @@ extern crate ws;
 use ws::{CloseCode, Factory, Handler, Message, Sender};
 use std::net::TcpStream;
 
-use std::io::{BufRead, BufReader};
 use std::io::{BufWriter, Write};
 
 pub struct Server {
     stream: TcpStream,
-    out: Sender,
 }
 
 impl Handler for Server {
     fn on_message(&mut self, msg: Message) -> ws::Result<()> {
         let mut writer = BufWriter::new(&self.stream);
-        if let Message::Text(txt) = msg {
-            writer.write(txt.as_bytes()).unwrap();
+        if let Message::Text(mut txt) = msg {
+            // ブラウザは改行を付けずに送ってくるので，サーバーが1行として読めるようにする
+            if !txt.ends_with('\n') {
+                txt.push('\n');
+            }
+            writer.write_all(txt.as_bytes()).unwrap();
             let _ = writer.flush();
         }
         Ok(())
@@ -36,10 +38,10 @@ pub struct MyFactory {
 }
 
 impl MyFactory {
-    pub fn new(ip: &String) -> Self {
+    pub fn new(ip: &str) -> Self {
         MyFactory {
             senders: Vec::new(),
-            ip: ip.clone(),
+            ip: ip.to_string(),
         }
     }
 }
@@ -47,11 +49,8 @@ impl Factory for MyFactory {
     type Handler = Server;
 
     fn connection_made(&mut self, ws: Sender) -> Server {
-        self.senders.push(ws.clone());
+        self.senders.push(ws);
         let stream = TcpStream::connect(&self.ip).unwrap();
-        Server {
-            stream: stream,
-            out: ws,
-        }
+        Server { stream }
     }
 }
@@ -0,0 +1,37 @@
+//! Zobrist keys for `Quoridor::hash`.
+//!
+//! Keys are derived from their index with splitmix64 instead of being stored in a table,
+//! so they are the same in every process and for every board size.
+use wall::Dir;
+
+const PAWN: u64 = 0;
+const WALL: u64 = 1 << 16;
+const WALL_NUM: u64 = 2 << 16;
+const TURN: u64 = 3 << 16;
+
+fn splitmix64(n: u64) -> u64 {
+    let mut z = n.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Key of player `id`'s pawn standing on square index `square`.
+pub fn pawn(id: usize, square: usize) -> u64 {
+    splitmix64(PAWN | (id as u64) << 8 | square as u64)
+}
+
+/// Key of a wall centred on wall index `slot`.
+pub fn wall(slot: usize, dir: Dir) -> u64 {
+    splitmix64(WALL | (slot as u64) << 1 | (dir == Dir::Vertical) as u64)
+}
+
+/// Key of player `id` having `num` walls left.
+pub fn wall_num(id: usize, num: usize) -> u64 {
+    splitmix64(WALL_NUM | (id as u64) << 12 | num as u64)
+}
+
+/// Key of player `id` being the side to move.
+pub fn turn(id: usize) -> u64 {
+    splitmix64(TURN | id as u64)
+}